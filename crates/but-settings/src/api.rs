@@ -18,6 +18,23 @@ pub struct FeatureFlagsUpdate {
     pub v3: Option<bool>,
 }
 
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+/// Update request for [`crate::app_settings::CodePushSettings`].
+pub struct CodePushUpdate {
+    pub disk_check_threshold_bytes: Option<u64>,
+    pub disk_safety_margin_bytes: Option<u64>,
+    pub max_batches_per_tick: Option<usize>,
+    pub max_refs_per_tick: Option<usize>,
+    pub max_seconds_per_tick: Option<u64>,
+    pub max_memory_bytes: Option<u64>,
+    pub network_retries: Option<u32>,
+    pub push_timeout_seconds: Option<u64>,
+    pub verify_target_push: Option<bool>,
+    pub push_debounce_millis: Option<u64>,
+    pub push_debounce_max_seconds: Option<u64>,
+}
+
 /// Mutation, immediately followed by writing everything to disk.
 impl AppSettingsWithDiskSync {
     pub fn update_onboarding_complete(&self, update: bool) -> Result<()> {
@@ -47,4 +64,42 @@ impl AppSettingsWithDiskSync {
         }
         settings.save()
     }
+
+    pub fn update_code_push(&self, update: CodePushUpdate) -> Result<()> {
+        let mut settings = self.get_mut_enforce_save()?;
+        if let Some(disk_check_threshold_bytes) = update.disk_check_threshold_bytes {
+            settings.code_push.disk_check_threshold_bytes = disk_check_threshold_bytes;
+        }
+        if let Some(disk_safety_margin_bytes) = update.disk_safety_margin_bytes {
+            settings.code_push.disk_safety_margin_bytes = disk_safety_margin_bytes;
+        }
+        if let Some(max_batches_per_tick) = update.max_batches_per_tick {
+            settings.code_push.max_batches_per_tick = max_batches_per_tick;
+        }
+        if let Some(max_refs_per_tick) = update.max_refs_per_tick {
+            settings.code_push.max_refs_per_tick = max_refs_per_tick;
+        }
+        if let Some(max_seconds_per_tick) = update.max_seconds_per_tick {
+            settings.code_push.max_seconds_per_tick = max_seconds_per_tick;
+        }
+        if let Some(max_memory_bytes) = update.max_memory_bytes {
+            settings.code_push.max_memory_bytes = max_memory_bytes;
+        }
+        if let Some(network_retries) = update.network_retries {
+            settings.code_push.network_retries = network_retries;
+        }
+        if let Some(push_timeout_seconds) = update.push_timeout_seconds {
+            settings.code_push.push_timeout_seconds = push_timeout_seconds;
+        }
+        if let Some(verify_target_push) = update.verify_target_push {
+            settings.code_push.verify_target_push = verify_target_push;
+        }
+        if let Some(push_debounce_millis) = update.push_debounce_millis {
+            settings.code_push.push_debounce_millis = push_debounce_millis;
+        }
+        if let Some(push_debounce_max_seconds) = update.push_debounce_max_seconds {
+            settings.code_push.push_debounce_max_seconds = push_debounce_max_seconds;
+        }
+        settings.save()
+    }
 }
@@ -24,3 +24,34 @@ pub struct FeatureFlags {
     /// Enables the v3 design, as well as the purgatory mode (no uncommitted diff ownership assignments).
     pub v3: bool,
 }
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct CodePushSettings {
+    /// Free disk space is only checked before first pushes that are estimated to need at least this many bytes.
+    pub disk_check_threshold_bytes: u64,
+    /// The amount of bytes that have to remain free on disk on top of the estimated size of a first push.
+    pub disk_safety_margin_bytes: u64,
+    /// The most batches of target history a single code push may send before pausing, or 0 for no limit.
+    pub max_batches_per_tick: usize,
+    /// The most refs a single code push may send before pausing, or 0 for no limit.
+    pub max_refs_per_tick: usize,
+    /// The most seconds a single code push may take before pausing, or 0 for no limit.
+    pub max_seconds_per_tick: u64,
+    /// The memory the app may use while pushing before batches of history are made smaller, or 0 for no limit.
+    pub max_memory_bytes: u64,
+    /// How often a push of the target history is retried after a network failure before giving up.
+    pub network_retries: u32,
+    /// The most seconds a single push to the server, or fetch from it, may take before failing as
+    /// network failure, or 0 to wait forever.
+    pub push_timeout_seconds: u64,
+    /// Whether the server is asked where the ref of a project points to after pushing its target,
+    /// to catch servers that accept pushes without applying them. This costs another round-trip.
+    pub verify_target_push: bool,
+    /// The milliseconds to wait for more changes of a project before pushing it, so bursts of changes
+    /// cause a single push. 0 pushes right away.
+    pub push_debounce_millis: u64,
+    /// The most seconds to hold back the push of a project that keeps changing, after which it's
+    /// pushed even if changes are still coming in.
+    pub push_debounce_max_seconds: u64,
+}
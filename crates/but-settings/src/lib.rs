@@ -17,6 +17,8 @@ pub struct AppSettings {
     pub github_oauth_app: app_settings::GitHubOAuthAppSettings,
     /// Application feature flags.
     pub feature_flags: app_settings::FeatureFlags,
+    /// Settings for pushing project code to GitButler.
+    pub code_push: app_settings::CodePushSettings,
 }
 
 impl Default for AppSettings {
@@ -1,9 +1,9 @@
 //! A generic UUID-based wrapper, via a newtype pattern
 //! with a few key integrations used throughout the library.
 
-use std::{fmt, hash::Hash, marker::PhantomData, str};
+use std::{borrow::Borrow, fmt, hash::Hash, marker::PhantomData, str};
 
-use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 use uuid::Uuid;
 
 /// A generic UUID-based newtype.
@@ -35,6 +35,106 @@ impl<T> Id<T> {
     pub fn generate() -> Self {
         Id(Uuid::new_v4(), PhantomData)
     }
+
+    /// Generate a new id via [`Uuid::now_v7`], which starts with the current time so ids
+    /// generated later sort after earlier ones.
+    ///
+    /// Parsing accepts any UUID version, so these can be used alongside ids from [`Id::generate`].
+    #[must_use]
+    pub fn generate_v7() -> Self {
+        Id(Uuid::now_v7(), PhantomData)
+    }
+
+    /// Return an id derived from `seed` alone, so the same seed always yields the same id, while
+    /// different seeds yield different ids.
+    ///
+    /// This is for tests and fixtures that need reproducible ids, like in snapshots, and must not
+    /// be used for real entities, as their ids would be as predictable as their seeds.
+    /// The id is a valid random-version UUID that parses and serializes like any other.
+    #[must_use]
+    pub fn from_seed(seed: u64) -> Self {
+        // Two steps of SplitMix64 spread the bits of the seed over all bytes of the UUID.
+        fn mix(mut z: u64) -> u64 {
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            z ^ (z >> 31)
+        }
+        let high = mix(seed.wrapping_add(0x9e37_79b9_7f4a_7c15));
+        let low = mix(seed.wrapping_add(0x3c6e_f372_fe94_f82a));
+        let bits = (u128::from(high) << 64) | u128::from(low);
+        Id(
+            uuid::Builder::from_random_bytes(bits.to_be_bytes()).into_uuid(),
+            PhantomData,
+        )
+    }
+
+    /// Return the id of the all-zero UUID, which is never generated and can thus stand for ids
+    /// that aren't set.
+    #[must_use]
+    pub const fn nil() -> Self {
+        Id(Uuid::nil(), PhantomData)
+    }
+
+    /// Return `true` if this is the [nil](Id::nil) id.
+    #[must_use]
+    pub fn is_nil(&self) -> bool {
+        self.0.is_nil()
+    }
+
+    /// Return the UUID this id wraps.
+    #[must_use]
+    pub fn into_inner(self) -> Uuid {
+        self.0
+    }
+
+    /// Return a reference to the UUID this id wraps.
+    #[must_use]
+    pub fn as_uuid(&self) -> &Uuid {
+        &self.0
+    }
+
+    /// Render the id as `<prefix>_<uuid>`, so ids of different kinds can be told apart in logs
+    /// and messages.
+    ///
+    /// [`Display`](fmt::Display) still renders the plain UUID, which is what's stored.
+    #[must_use]
+    pub fn to_prefixed(&self, prefix: &str) -> String {
+        format!("{prefix}_{}", self.0)
+    }
+
+    /// Parse `s` as rendered by [`Id::to_prefixed`], failing if it doesn't start with
+    /// `expected_prefix`.
+    pub fn from_prefixed(expected_prefix: &str, s: &str) -> Result<Self, PrefixedIdError> {
+        let uuid = s
+            .strip_prefix(expected_prefix)
+            .and_then(|rest| rest.strip_prefix('_'))
+            .ok_or_else(|| PrefixedIdError::Prefix {
+                expected: expected_prefix.to_owned(),
+                actual: s.to_owned(),
+            })?;
+        Uuid::parse_str(uuid)
+            .map(Into::into)
+            .map_err(PrefixedIdError::Uuid)
+    }
+
+    /// Parse `s` like [`FromStr`](str::FromStr) does, but also with surrounding whitespace, and
+    /// with hyphens anywhere or not at all, as UUIDs pasted or taken from other tools may have them.
+    ///
+    /// It still fails unless 32 hex digits are left once braces around them are removed as well.
+    /// [`FromStr`](str::FromStr) and [`Deserialize`] don't do this, so stored data that merely
+    /// looks like an id isn't read as one.
+    pub fn parse_lenient(s: &str) -> Result<Self, uuid::Error> {
+        let s = s.trim();
+        if let Ok(uuid) = Uuid::parse_str(s) {
+            return Ok(uuid.into());
+        }
+        let s = s
+            .strip_prefix('{')
+            .and_then(|s| s.strip_suffix('}'))
+            .unwrap_or(s);
+        let digits: String = s.chars().filter(|c| *c != '-').collect();
+        Uuid::parse_str(&digits).map(Into::into)
+    }
 }
 
 impl<T> Default for Id<T> {
@@ -57,21 +157,110 @@ impl<T> From<Uuid> for Id<T> {
     }
 }
 
+impl<T> AsRef<Uuid> for Id<T> {
+    fn as_ref(&self) -> &Uuid {
+        &self.0
+    }
+}
+
+/// Ids hash and compare like their UUID, so they can be used to look up maps keyed by UUID.
+impl<T> Borrow<Uuid> for Id<T> {
+    fn borrow(&self) -> &Uuid {
+        &self.0
+    }
+}
+
+/// Ids are read from UUID strings, or from the 16 bytes of the UUID with formats that aren't
+/// human-readable, which also accept strings so data written before still reads.
 impl<'de, T> Deserialize<'de> for Id<T> {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: Deserializer<'de>,
     {
-        Uuid::deserialize(deserializer).map(Into::into)
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(IdVisitor(PhantomData))
+        } else {
+            deserializer.deserialize_bytes(IdVisitor(PhantomData))
+        }
     }
 }
 
+struct IdVisitor<T>(PhantomData<T>);
+
+impl<T> de::Visitor<'_> for IdVisitor<T> {
+    type Value = Id<T>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a UUID string or its 16 bytes")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        Uuid::parse_str(v).map(Into::into).map_err(E::custom)
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+        match Uuid::from_slice(v) {
+            Ok(uuid) => Ok(uuid.into()),
+            // Formats without strings of their own store them as bytes.
+            Err(_) => match str::from_utf8(v) {
+                Ok(v) => self.visit_str(v),
+                Err(_) => Err(E::invalid_length(v.len(), &self)),
+            },
+        }
+    }
+}
+
+/// Ids are written as UUID strings, but as the 16 bytes of the UUID with formats that aren't
+/// human-readable, which keeps them compact.
 impl<T> Serialize for Id<T> {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        self.0.serialize(serializer)
+        if serializer.is_human_readable() {
+            serializer.collect_str(&self.0)
+        } else {
+            serializer.serialize_bytes(self.0.as_bytes())
+        }
+    }
+}
+
+/// Ids are described as UUID-formatted string, just like they are serialized.
+#[cfg(feature = "schemars")]
+impl<T> schemars::JsonSchema for Id<T> {
+    fn is_referenceable() -> bool {
+        false
+    }
+
+    fn schema_name() -> String {
+        "Id".to_owned()
+    }
+
+    fn json_schema(_gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        schemars::schema::SchemaObject {
+            instance_type: Some(schemars::schema::InstanceType::String.into()),
+            format: Some("uuid".to_owned()),
+            ..Default::default()
+        }
+        .into()
+    }
+}
+
+/// Arbitrary ids wrap random UUIDs, like those of [`Id::generate`], for any kind of id.
+#[cfg(feature = "proptest")]
+impl<T> proptest::arbitrary::Arbitrary for Id<T> {
+    type Parameters = ();
+    type Strategy =
+        proptest::strategy::Map<proptest::arbitrary::StrategyFor<u128>, fn(u128) -> Self>;
+
+    fn arbitrary_with(_args: Self::Parameters) -> Self::Strategy {
+        use proptest::strategy::Strategy;
+
+        proptest::arbitrary::any::<u128>().prop_map(|bits| {
+            uuid::Builder::from_random_bytes(bits.to_be_bytes())
+                .into_uuid()
+                .into()
+        })
     }
 }
 
@@ -102,3 +291,35 @@ impl<T> str::FromStr for Id<T> {
         Uuid::parse_str(s).map(Into::into)
     }
 }
+
+/// The error returned by [`Id::from_prefixed`].
+#[derive(Debug)]
+pub enum PrefixedIdError {
+    /// `actual` doesn't start with the `expected` prefix.
+    Prefix { expected: String, actual: String },
+    /// The part after the prefix isn't a valid UUID.
+    Uuid(uuid::Error),
+}
+
+impl fmt::Display for PrefixedIdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PrefixedIdError::Prefix { expected, actual } => {
+                write!(
+                    f,
+                    "expected an id prefixed with '{expected}_', got '{actual}'"
+                )
+            }
+            PrefixedIdError::Uuid(err) => write!(f, "invalid id: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for PrefixedIdError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PrefixedIdError::Prefix { .. } => None,
+            PrefixedIdError::Uuid(err) => Some(err),
+        }
+    }
+}
@@ -0,0 +1,258 @@
+use gitbutler_id::id::Id;
+
+struct Kind;
+
+mod generate_v7 {
+    use super::*;
+
+    #[test]
+    fn ids_sort_by_creation() {
+        let ids: Vec<Id<Kind>> = (0..100).map(|_| Id::generate_v7()).collect();
+        let mut sorted = ids.clone();
+        sorted.sort();
+        assert_eq!(sorted, ids);
+        assert_eq!(
+            ids[0].to_string().parse::<Id<Kind>>().unwrap(),
+            ids[0],
+            "v7 ids round-trip"
+        );
+    }
+
+    #[test]
+    fn v4_ids_still_parse() {
+        let id = Id::<Kind>::generate();
+        assert_eq!(id.to_string().parse::<Id<Kind>>().unwrap(), id);
+        let deserialized: Id<Kind> = serde_json::from_str(&format!("\"{id}\"")).unwrap();
+        assert_eq!(deserialized, id);
+    }
+}
+
+mod nil {
+    use super::*;
+
+    #[test]
+    fn is_only_the_all_zero_uuid() {
+        let nil = Id::<Kind>::nil();
+        assert!(nil.is_nil());
+        assert_eq!(nil.into_inner(), uuid::Uuid::nil());
+        assert!(!Id::<Kind>::generate().is_nil());
+        assert!(!Id::<Kind>::generate_v7().is_nil());
+    }
+
+    #[test]
+    fn round_trips() {
+        let nil = Id::<Kind>::nil();
+        assert_eq!(
+            nil.to_string(),
+            "00000000-0000-0000-0000-000000000000",
+            "it's rendered like any other id"
+        );
+        assert!(nil.to_string().parse::<Id<Kind>>().unwrap().is_nil());
+        let deserialized: Id<Kind> =
+            serde_json::from_str(&serde_json::to_string(&nil).unwrap()).unwrap();
+        assert!(deserialized.is_nil());
+    }
+}
+
+mod from_seed {
+    use super::*;
+
+    #[test]
+    fn same_seeds_yield_the_same_id() {
+        assert_eq!(Id::<Kind>::from_seed(42), Id::<Kind>::from_seed(42));
+        assert_ne!(Id::<Kind>::from_seed(42), Id::<Kind>::from_seed(43));
+        assert_ne!(Id::<Kind>::from_seed(0), Id::<Kind>::from_seed(1));
+        assert!(!Id::<Kind>::from_seed(0).is_nil());
+    }
+
+    #[test]
+    fn round_trips() {
+        let id = Id::<Kind>::from_seed(7);
+        assert_eq!(
+            id.into_inner().get_version(),
+            Some(uuid::Version::Random),
+            "it's a valid UUID"
+        );
+        assert_eq!(id.to_string().parse::<Id<Kind>>().unwrap(), id);
+        let deserialized: Id<Kind> =
+            serde_json::from_str(&serde_json::to_string(&id).unwrap()).unwrap();
+        assert_eq!(deserialized, id);
+    }
+}
+
+mod prefixed {
+    use gitbutler_id::id::PrefixedIdError;
+
+    use super::*;
+
+    #[test]
+    fn round_trips() {
+        let id = Id::<Kind>::generate();
+        let prefixed = id.to_prefixed("proj");
+        assert_eq!(prefixed, format!("proj_{id}"));
+        assert_eq!(Id::<Kind>::from_prefixed("proj", &prefixed).unwrap(), id);
+        assert_eq!(
+            id.to_string().parse::<Id<Kind>>().unwrap(),
+            id,
+            "display stays plain"
+        );
+    }
+
+    #[test]
+    fn mismatched_prefixes_are_errors() {
+        let prefixed = Id::<Kind>::generate().to_prefixed("user");
+        assert!(matches!(
+            Id::<Kind>::from_prefixed("proj", &prefixed),
+            Err(PrefixedIdError::Prefix { .. })
+        ));
+        assert!(matches!(
+            Id::<Kind>::from_prefixed("use", &prefixed),
+            Err(PrefixedIdError::Prefix { .. })
+        ));
+        assert!(matches!(
+            Id::<Kind>::from_prefixed("proj", "proj_not-a-uuid"),
+            Err(PrefixedIdError::Uuid(_))
+        ));
+    }
+}
+
+mod parse_lenient {
+    use super::*;
+
+    #[test]
+    fn other_forms_yield_the_same_id() {
+        let id = Id::<Kind>::generate();
+        let hyphenless = id.as_uuid().simple().to_string();
+        for s in [
+            id.to_string(),
+            hyphenless.clone(),
+            hyphenless.to_uppercase(),
+            format!("{{{id}}}"),
+            format!("{{{hyphenless}}}"),
+            format!("  {id}\n"),
+            format!("\t{{{hyphenless}}} "),
+            format!("{}-{}", &hyphenless[..4], &hyphenless[4..]),
+            format!(" urn:uuid:{id}"),
+        ] {
+            assert_eq!(Id::<Kind>::parse_lenient(&s).unwrap(), id, "{s:?}");
+        }
+        assert!(
+            format!(" {id}").parse::<Id<Kind>>().is_err(),
+            "parsing as usual stays strict"
+        );
+    }
+
+    #[test]
+    fn anything_but_a_uuid_is_an_error() {
+        let id = Id::<Kind>::generate();
+        for s in [
+            String::new(),
+            "  ".into(),
+            "{}".into(),
+            "not-a-uuid".into(),
+            format!("{{{id}"),
+            format!("{id}0"),
+            id.to_string()[1..].to_owned(),
+            format!("{{ {id} }}"),
+            id.to_string().replace(|c: char| c.is_ascii_digit(), "g"),
+        ] {
+            assert!(Id::<Kind>::parse_lenient(&s).is_err(), "{s:?}");
+        }
+    }
+}
+
+mod raw_uuid {
+    use std::collections::{HashMap, HashSet};
+
+    use super::*;
+
+    #[test]
+    fn ids_look_up_maps_keyed_by_uuid() {
+        let id = Id::<Kind>::generate();
+        let map: HashMap<uuid::Uuid, &str> = [(*id.as_uuid(), "value")].into();
+        assert_eq!(map.get(id.as_ref()), Some(&"value"));
+        assert_eq!(map.get(&id.into_inner()), Some(&"value"));
+
+        // With `Borrow`, sets of ids can be queried with plain UUIDs.
+        let ids = HashSet::from([id]);
+        assert!(ids.contains(id.as_uuid()));
+        assert_eq!(Id::<Kind>::from(id.into_inner()), id);
+    }
+}
+
+mod serialization {
+    use super::*;
+
+    #[test]
+    fn human_readable_formats_use_strings() {
+        let id = Id::<Kind>::generate();
+        let json = serde_json::to_string(&id).unwrap();
+        assert_eq!(json, format!("\"{id}\""));
+        assert_eq!(serde_json::from_str::<Id<Kind>>(&json).unwrap(), id);
+    }
+
+    #[test]
+    fn binary_formats_use_bytes() {
+        let id = Id::<Kind>::generate();
+        let bytes = bincode::serialize(&id).unwrap();
+        assert_eq!(
+            bytes,
+            [&16u64.to_le_bytes()[..], id.as_uuid().as_bytes()].concat(),
+            "just the length and the 16 bytes of the UUID"
+        );
+        assert_eq!(bincode::deserialize::<Id<Kind>>(&bytes).unwrap(), id);
+    }
+
+    #[test]
+    fn binary_formats_still_read_strings() {
+        let id = Id::<Kind>::generate();
+        let bytes = bincode::serialize(&id.to_string()).unwrap();
+        assert_eq!(bincode::deserialize::<Id<Kind>>(&bytes).unwrap(), id);
+        assert!(bincode::deserialize::<Id<Kind>>(&bincode::serialize("nope").unwrap()).is_err());
+    }
+}
+
+#[cfg(feature = "schemars")]
+mod json_schema {
+    use super::*;
+
+    #[test]
+    fn ids_are_uuid_strings() {
+        let schema = serde_json::to_value(schemars::schema_for!(Id<Kind>)).unwrap();
+        assert_eq!(schema["type"], "string");
+        assert_eq!(schema["format"], "uuid");
+        assert!(
+            !schema.to_string().contains("PhantomData"),
+            "the kind of id isn't part of the schema: {schema}"
+        );
+    }
+}
+
+#[cfg(feature = "proptest")]
+mod arbitrary {
+    use proptest::prelude::*;
+    use proptest_derive::Arbitrary;
+
+    use super::*;
+
+    struct OtherKind;
+
+    #[derive(Debug, Arbitrary)]
+    struct Ids {
+        kind: Id<Kind>,
+        other: Id<OtherKind>,
+    }
+
+    proptest! {
+        #[test]
+        fn round_trips_through_strings(id: Id<Kind>) {
+            prop_assert_eq!(id.to_string().parse::<Id<Kind>>().unwrap(), id);
+        }
+
+        #[test]
+        fn composes_with_derived_impls(ids: Ids) {
+            prop_assert_eq!(ids.kind.as_uuid().get_version_num(), 4);
+            prop_assert_eq!(ids.other.to_string().parse::<Id<OtherKind>>().unwrap(), ids.other);
+        }
+    }
+}
@@ -3,7 +3,10 @@ use std::path::PathBuf;
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 
-use crate::{ApiProject, AuthKey, CodePushState, FetchResult, Project, ProjectId};
+use crate::{
+    ApiProject, AuthKey, CodePushBackend, CodePushMirror, CodePushState, CodeSyncMode, FetchResult,
+    Project, ProjectId, PushError, RefRetention,
+};
 
 const PROJECTS_FILE: &str = "projects.json";
 
@@ -25,6 +28,47 @@ pub struct UpdateRequest {
     pub preferred_key: Option<AuthKey>,
     pub ok_with_force_push: Option<bool>,
     pub gitbutler_code_push_state: Option<CodePushState>,
+    #[serde(default = "default_false")]
+    pub unset_gitbutler_code_push_state: bool,
+    pub last_push_error: Option<PushError>,
+    #[serde(default = "default_false")]
+    pub unset_last_push_error: bool,
+    pub code_sync_mode: Option<CodeSyncMode>,
+    pub push_submodules: Option<bool>,
+    pub ref_retention: Option<RefRetention>,
+    pub code_push_backend: Option<CodePushBackend>,
+    pub code_push_batch_size: Option<usize>,
+    #[serde(default = "default_false")]
+    pub unset_code_push_batch_size: bool,
+    pub code_push_min_interval_seconds: Option<u64>,
+    #[serde(default = "default_false")]
+    pub unset_code_push_min_interval_seconds: bool,
+    pub code_push_max_object_bytes: Option<u64>,
+    #[serde(default = "default_false")]
+    pub unset_code_push_max_object_bytes: bool,
+    pub code_push_max_bytes_per_second: Option<u64>,
+    #[serde(default = "default_false")]
+    pub unset_code_push_max_bytes_per_second: bool,
+    pub code_push_proxy_url: Option<String>,
+    #[serde(default = "default_false")]
+    pub unset_code_push_proxy_url: bool,
+    pub code_push_ssh_key_path: Option<PathBuf>,
+    #[serde(default = "default_false")]
+    pub unset_code_push_ssh_key_path: bool,
+    pub code_push_excluded_refs: Option<Vec<String>>,
+    pub allow_force_push: Option<bool>,
+    pub code_push_batch_ref_prefix: Option<String>,
+    #[serde(default = "default_false")]
+    pub unset_code_push_batch_ref_prefix: bool,
+    pub code_push_target_ref_prefix: Option<String>,
+    #[serde(default = "default_false")]
+    pub unset_code_push_target_ref_prefix: bool,
+    pub code_push_mirrors: Option<Vec<CodePushMirror>>,
+    /// The state of the last push to the mirror of the same URL, if the project still has it.
+    pub code_push_mirror_state: Option<CodePushMirror>,
+    pub account_id: Option<u64>,
+    #[serde(default = "default_false")]
+    pub unset_account_id: bool,
     pub project_data_last_fetched: Option<FetchResult>,
     pub omit_certificate_check: Option<bool>,
     pub use_diff_context: Option<bool>,
@@ -117,8 +161,132 @@ impl Storage {
             project.project_data_last_fetch = Some(project_data_last_fetched.clone());
         }
 
-        if let Some(state) = update_request.gitbutler_code_push_state {
-            project.gitbutler_code_push_state = Some(state);
+        if let Some(state) = &update_request.gitbutler_code_push_state {
+            project.gitbutler_code_push_state = Some(state.clone());
+        }
+
+        if update_request.unset_gitbutler_code_push_state {
+            project.gitbutler_code_push_state = None;
+        }
+
+        if let Some(last_push_error) = &update_request.last_push_error {
+            project.last_push_error = Some(last_push_error.clone());
+        }
+
+        if update_request.unset_last_push_error {
+            project.last_push_error = None;
+        }
+
+        if let Some(code_sync_mode) = update_request.code_sync_mode {
+            project.code_sync_mode = code_sync_mode;
+        }
+
+        if let Some(push_submodules) = update_request.push_submodules {
+            project.push_submodules = push_submodules;
+        }
+
+        if let Some(ref_retention) = update_request.ref_retention {
+            project.ref_retention = ref_retention;
+        }
+
+        if let Some(code_push_backend) = &update_request.code_push_backend {
+            project.code_push_backend = code_push_backend.clone();
+        }
+
+        if let Some(code_push_batch_size) = update_request.code_push_batch_size {
+            project.code_push_batch_size = Some(code_push_batch_size);
+        }
+
+        if update_request.unset_code_push_batch_size {
+            project.code_push_batch_size = None;
+        }
+
+        if let Some(code_push_min_interval_seconds) = update_request.code_push_min_interval_seconds
+        {
+            project.code_push_min_interval_seconds = Some(code_push_min_interval_seconds);
+        }
+
+        if update_request.unset_code_push_min_interval_seconds {
+            project.code_push_min_interval_seconds = None;
+        }
+
+        if let Some(code_push_max_object_bytes) = update_request.code_push_max_object_bytes {
+            project.code_push_max_object_bytes = Some(code_push_max_object_bytes);
+        }
+
+        if update_request.unset_code_push_max_object_bytes {
+            project.code_push_max_object_bytes = None;
+        }
+
+        if let Some(code_push_max_bytes_per_second) = update_request.code_push_max_bytes_per_second
+        {
+            project.code_push_max_bytes_per_second = Some(code_push_max_bytes_per_second);
+        }
+
+        if update_request.unset_code_push_max_bytes_per_second {
+            project.code_push_max_bytes_per_second = None;
+        }
+
+        if let Some(code_push_proxy_url) = &update_request.code_push_proxy_url {
+            project.code_push_proxy_url = Some(code_push_proxy_url.clone());
+        }
+
+        if update_request.unset_code_push_proxy_url {
+            project.code_push_proxy_url = None;
+        }
+
+        if let Some(code_push_ssh_key_path) = &update_request.code_push_ssh_key_path {
+            project.code_push_ssh_key_path = Some(code_push_ssh_key_path.clone());
+        }
+
+        if update_request.unset_code_push_ssh_key_path {
+            project.code_push_ssh_key_path = None;
+        }
+
+        if let Some(code_push_excluded_refs) = &update_request.code_push_excluded_refs {
+            project.code_push_excluded_refs = code_push_excluded_refs.clone();
+        }
+
+        if let Some(allow_force_push) = update_request.allow_force_push {
+            *project.allow_force_push = allow_force_push;
+        }
+
+        if let Some(code_push_batch_ref_prefix) = &update_request.code_push_batch_ref_prefix {
+            project.code_push_batch_ref_prefix = Some(code_push_batch_ref_prefix.clone());
+        }
+
+        if update_request.unset_code_push_batch_ref_prefix {
+            project.code_push_batch_ref_prefix = None;
+        }
+
+        if let Some(code_push_target_ref_prefix) = &update_request.code_push_target_ref_prefix {
+            project.code_push_target_ref_prefix = Some(code_push_target_ref_prefix.clone());
+        }
+
+        if update_request.unset_code_push_target_ref_prefix {
+            project.code_push_target_ref_prefix = None;
+        }
+
+        if let Some(code_push_mirrors) = &update_request.code_push_mirrors {
+            project.code_push_mirrors = code_push_mirrors.clone();
+        }
+
+        if let Some(updated) = &update_request.code_push_mirror_state {
+            if let Some(mirror) = project
+                .code_push_mirrors
+                .iter_mut()
+                .find(|mirror| mirror.url == updated.url)
+            {
+                mirror.state = updated.state.clone();
+            }
+        }
+
+        if let Some(account_id) = update_request.account_id {
+            project.account_id = Some(account_id);
+        }
+
+        if update_request.unset_account_id {
+            project.account_id = None;
         }
 
         if let Some(ok_with_force_push) = update_request.ok_with_force_push {
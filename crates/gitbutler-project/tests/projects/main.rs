@@ -164,3 +164,101 @@ mod delete {
         assert!(!project.gb_dir().exists());
     }
 }
+
+mod update {
+    use gitbutler_project::UpdateRequest;
+
+    use super::*;
+
+    #[test]
+    fn bandwidth_caps_can_be_unset() {
+        let (controller, _tmp) = new();
+        let repository = gitbutler_testsupport::TestProject::default();
+        let project = controller.add(repository.path()).unwrap();
+
+        let project = controller
+            .update(&UpdateRequest {
+                id: project.id,
+                code_push_max_bytes_per_second: Some(1024),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(project.code_push_max_bytes_per_second, Some(1024));
+
+        let project = controller
+            .update(&UpdateRequest {
+                id: project.id,
+                unset_code_push_max_bytes_per_second: true,
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(project.code_push_max_bytes_per_second, None);
+        assert_eq!(
+            controller
+                .get(project.id)
+                .unwrap()
+                .code_push_max_bytes_per_second,
+            None,
+            "pushes run at full speed again"
+        );
+    }
+    #[test]
+    fn object_size_limits_can_be_unset() {
+        let (controller, _tmp) = new();
+        let repository = gitbutler_testsupport::TestProject::default();
+        let project = controller.add(repository.path()).unwrap();
+
+        let project = controller
+            .update(&UpdateRequest {
+                id: project.id,
+                code_push_max_object_bytes: Some(1024),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(project.code_push_max_object_bytes, Some(1024));
+
+        controller
+            .update(&UpdateRequest {
+                id: project.id,
+                unset_code_push_max_object_bytes: true,
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(
+            controller
+                .get(project.id)
+                .unwrap()
+                .code_push_max_object_bytes,
+            None,
+            "files of any size are pushed again"
+        );
+    }
+    #[test]
+    fn ref_prefixes_can_be_unset() {
+        let (controller, _tmp) = new();
+        let repository = gitbutler_testsupport::TestProject::default();
+        let project = controller.add(repository.path()).unwrap();
+
+        controller
+            .update(&UpdateRequest {
+                id: project.id,
+                code_push_batch_ref_prefix: Some("refs/custom/batches".into()),
+                code_push_target_ref_prefix: Some("refs/custom/targets".into()),
+                ..Default::default()
+            })
+            .unwrap();
+        let project = controller
+            .update(&UpdateRequest {
+                id: project.id,
+                unset_code_push_batch_ref_prefix: true,
+                unset_code_push_target_ref_prefix: true,
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(project.code_push_batch_ref_prefix, None);
+        assert_eq!(
+            project.code_push_target_ref_prefix, None,
+            "the default namespaces are used again"
+        );
+    }
+}
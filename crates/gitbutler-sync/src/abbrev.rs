@@ -0,0 +1,58 @@
+//! Short forms of object ids, which keep logs easy to scan.
+
+/// The length of abbreviated object ids, which is what `git` uses by default.
+pub(crate) const DEFAULT_LEN: usize = 7;
+
+/// The shortest abbreviation `git` produces, which is used for shorter lengths as well.
+const MIN_LEN: usize = 4;
+
+/// Abbreviating object ids like `git rev-parse --short` does in repositories where they are unique.
+pub(crate) trait Abbreviate {
+    /// Return the first `len` hex characters of the id, but at least 4 like `git`, and at most all of them.
+    fn abbreviate(&self, len: usize) -> String;
+
+    /// Return the first [`DEFAULT_LEN`] hex characters of the id.
+    fn short(&self) -> String {
+        self.abbreviate(DEFAULT_LEN)
+    }
+}
+
+impl Abbreviate for git2::Oid {
+    fn abbreviate(&self, len: usize) -> String {
+        let mut hex = self.to_string();
+        hex.truncate(len.max(MIN_LEN));
+        hex
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn git_short(repo: &git2::Repository, len: usize) -> String {
+        let output = std::process::Command::new("git")
+            .args(["rev-parse", &format!("--short={len}"), "HEAD"])
+            .current_dir(repo.path())
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap().trim().to_owned()
+    }
+
+    #[test]
+    fn abbreviations_match_git() {
+        let (repo, _tmp) = gitbutler_testsupport::test_repository();
+        let head = repo.head().unwrap().target().unwrap();
+        assert_eq!(head.short(), git_short(&repo, DEFAULT_LEN));
+        for len in [1, 4, 12, 40] {
+            assert_eq!(head.abbreviate(len), git_short(&repo, len), "{len}");
+        }
+    }
+
+    #[test]
+    fn lengths_beyond_the_id_are_the_full_id() {
+        let id = git2::Oid::from_str("1111111111111111111111111111111111111111").unwrap();
+        assert_eq!(id.abbreviate(100), id.to_string());
+        assert_eq!(id.abbreviate(usize::MAX), id.to_string());
+    }
+}
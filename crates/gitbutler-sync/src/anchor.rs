@@ -0,0 +1,69 @@
+//! The commit ending the last batch of the target history that was pushed, which is stored after
+//! each batch as the push state is only written every few batches.
+//!
+//! After a crash, the next push resumes after this commit instead of after the commit of the
+//! push state, so fewer batches are pushed again.
+use std::path::PathBuf;
+
+use anyhow::Result;
+use gitbutler_project::Project;
+
+/// Return the commit ending the last batch pushed for `project`, if there is one.
+pub(crate) fn load(project: &Project) -> Result<Option<git2::Oid>> {
+    match std::fs::read_to_string(anchor_path(project)) {
+        Ok(anchor) => Ok(Some(anchor.trim().parse()?)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+pub(crate) fn save(project: &Project, id: git2::Oid) -> Result<()> {
+    Ok(gitbutler_fs::create_dirs_then_write(
+        anchor_path(project),
+        id.to_string(),
+    )?)
+}
+
+/// Forget the last pushed batch of `project`, once the push state has caught up with it or
+/// doesn't build on it anymore.
+pub(crate) fn forget(project: &Project) -> Result<()> {
+    match std::fs::remove_file(anchor_path(project)) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Return the commit after which the target history of `project` up to `target` is pushed, given
+/// `last_pushed` of the push state.
+///
+/// That's the stored anchor if it's part of the history of `target` and newer than `last_pushed`,
+/// or `last_pushed` otherwise, e.g. if the target was reset.
+pub(crate) fn resume_point(
+    repo: &git2::Repository,
+    project: &Project,
+    target: git2::Oid,
+    last_pushed: Option<git2::Oid>,
+) -> Result<Option<git2::Oid>> {
+    let Some(anchor) = load(project)? else {
+        return Ok(last_pushed);
+    };
+    let in_target = anchor == target || repo.graph_descendant_of(target, anchor).unwrap_or(false);
+    let newer = last_pushed.is_none_or(|last_pushed| {
+        repo.graph_descendant_of(anchor, last_pushed)
+            .unwrap_or(false)
+    });
+    if in_target && newer {
+        tracing::info!(
+            project_id = %project.id,
+            "resuming code push after the last pushed batch",
+        );
+        Ok(Some(anchor))
+    } else {
+        forget(project)?;
+        Ok(last_pushed)
+    }
+}
+
+fn anchor_path(project: &Project) -> PathBuf {
+    crate::mirror::state_path(project, "code_push_anchor")
+}
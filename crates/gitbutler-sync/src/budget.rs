@@ -0,0 +1,84 @@
+//! Bounding the work a single code push does, so large pushes are spread over multiple watcher ticks.
+use std::time::{Duration, Instant};
+
+use but_settings::app_settings::CodePushSettings;
+
+/// How much work a single code push may do before it pauses, to be resumed by the next one.
+///
+/// Limits that are `None` don't apply, which is what the default does.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PushBudget {
+    /// The most batches of target history to push.
+    pub max_batches: Option<usize>,
+    /// The most refs to push.
+    pub max_refs: Option<usize>,
+    /// The longest time to spend pushing.
+    pub max_duration: Option<Duration>,
+}
+
+impl PushBudget {
+    /// Obtain the budget configured in `settings`, where 0 means there is no limit.
+    pub fn from_settings(settings: &CodePushSettings) -> Self {
+        PushBudget {
+            max_batches: (settings.max_batches_per_tick > 0)
+                .then_some(settings.max_batches_per_tick),
+            max_refs: (settings.max_refs_per_tick > 0).then_some(settings.max_refs_per_tick),
+            max_duration: (settings.max_seconds_per_tick > 0)
+                .then(|| Duration::from_secs(settings.max_seconds_per_tick)),
+        }
+    }
+}
+
+/// The result of a code push.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PushOutcome {
+    /// Everything was pushed, or there was nothing to push.
+    Complete,
+    /// The [`PushBudget`] was used up, and the progress was stored to resume with the next push.
+    Paused,
+    /// The push was cancelled between two batches of the target history, with the last pushed one stored
+    /// to resume from. Refs aren't pushed, as they may point to history the server doesn't have yet.
+    Cancelled,
+}
+
+/// Keeps track of how much of a [`PushBudget`] was used.
+pub(crate) struct BudgetTracker {
+    budget: PushBudget,
+    started: Instant,
+    batches: usize,
+}
+
+impl BudgetTracker {
+    pub(crate) fn new(budget: PushBudget) -> Self {
+        BudgetTracker {
+            budget,
+            started: Instant::now(),
+            batches: 0,
+        }
+    }
+
+    /// Return `true` if another batch may be pushed, and account for it.
+    ///
+    /// The first batch is always allowed so each push makes progress.
+    pub(crate) fn take_batch(&mut self) -> bool {
+        let allowed = self.batches == 0
+            || (!self.out_of_time()
+                && self.budget.max_batches.is_none_or(|max| self.batches < max));
+        if allowed {
+            self.batches += 1;
+        }
+        allowed
+    }
+
+    /// Return `true` if the time allotted to the push is up.
+    pub(crate) fn out_of_time(&self) -> bool {
+        self.budget
+            .max_duration
+            .is_some_and(|max| self.started.elapsed() >= max)
+    }
+
+    /// The most refs to push, which is at least one so each push makes progress.
+    pub(crate) fn max_refs(&self) -> usize {
+        self.budget.max_refs.map_or(usize::MAX, |max| max.max(1))
+    }
+}
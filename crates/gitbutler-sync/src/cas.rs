@@ -0,0 +1,232 @@
+//! Pushing code to a content-addressed store instead of a git server.
+//!
+//! Objects are stored individually, keyed by their hash, and each pushed ref is represented by a
+//! small manifest pointing to its target. An object is only ever stored after all objects it refers
+//! to, so having an object in the store implies having its whole history.
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// What's stored for each ref.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RefManifest {
+    /// The object the ref points to.
+    #[serde(with = "gitbutler_serde::oid")]
+    pub target: git2::Oid,
+}
+
+/// A store for objects keyed by their hash, along with a manifest for each ref.
+pub trait ObjectStore {
+    /// Return `true` if the object with `id` is stored.
+    fn contains(&self, id: git2::Oid) -> Result<bool>;
+    /// Store `data` of an object of `kind` as `id`.
+    fn write(&self, id: git2::Oid, kind: git2::ObjectType, data: &[u8]) -> Result<()>;
+    /// Read the object with `id`, or `None` if it isn't stored.
+    fn read(&self, id: git2::Oid) -> Result<Option<(git2::ObjectType, Vec<u8>)>>;
+    /// Store `manifest` for the ref named `refname`.
+    fn write_manifest(&self, refname: &str, manifest: &RefManifest) -> Result<()>;
+    /// Read the manifest of the ref named `refname`, or `None` if there is none.
+    fn read_manifest(&self, refname: &str) -> Result<Option<RefManifest>>;
+    /// Remove the manifest of the ref named `refname`, if it exists.
+    fn delete_manifest(&self, refname: &str) -> Result<()>;
+}
+
+/// An [`ObjectStore`] in a directory, laid out like a bucket of an S3-compatible object store.
+///
+/// Objects are kept at `objects/<hex-id>` in the uncompressed loose-object format of git,
+/// and manifests are kept at `<refname>.json`, e.g. `refs/heads/main.json`.
+#[derive(Debug, Clone)]
+pub struct DirectoryStore {
+    root: PathBuf,
+}
+
+impl DirectoryStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        DirectoryStore { root: root.into() }
+    }
+
+    fn object_path(&self, id: git2::Oid) -> PathBuf {
+        self.root.join("objects").join(id.to_string())
+    }
+
+    fn manifest_path(&self, refname: &str) -> Result<PathBuf> {
+        if !git2::Reference::is_valid_name(refname) {
+            bail!("'{refname}' isn't a valid ref name");
+        }
+        Ok(self.root.join(format!("{refname}.json")))
+    }
+}
+
+impl ObjectStore for DirectoryStore {
+    fn contains(&self, id: git2::Oid) -> Result<bool> {
+        Ok(self.object_path(id).try_exists()?)
+    }
+
+    fn write(&self, id: git2::Oid, kind: git2::ObjectType, data: &[u8]) -> Result<()> {
+        let mut object = format!("{} {}\0", kind.str(), data.len()).into_bytes();
+        object.extend_from_slice(data);
+        Ok(gitbutler_fs::create_dirs_then_write(
+            self.object_path(id),
+            object,
+        )?)
+    }
+
+    fn read(&self, id: git2::Oid) -> Result<Option<(git2::ObjectType, Vec<u8>)>> {
+        let Some(object) = read_if_exists(&self.object_path(id))? else {
+            return Ok(None);
+        };
+        let nul = object
+            .iter()
+            .position(|b| *b == 0)
+            .with_context(|| format!("object {id} has no header"))?;
+        let header = std::str::from_utf8(&object[..nul])?;
+        let kind = header
+            .split_once(' ')
+            .and_then(|(kind, _len)| git2::ObjectType::from_str(kind))
+            .with_context(|| format!("object {id} has an invalid header: '{header}'"))?;
+        Ok(Some((kind, object[nul + 1..].to_vec())))
+    }
+
+    fn write_manifest(&self, refname: &str, manifest: &RefManifest) -> Result<()> {
+        Ok(gitbutler_fs::create_dirs_then_write(
+            self.manifest_path(refname)?,
+            serde_json::to_vec(manifest)?,
+        )?)
+    }
+
+    fn read_manifest(&self, refname: &str) -> Result<Option<RefManifest>> {
+        read_if_exists(&self.manifest_path(refname)?)?
+            .map(|manifest| serde_json::from_slice(&manifest).map_err(Into::into))
+            .transpose()
+    }
+
+    fn delete_manifest(&self, refname: &str) -> Result<()> {
+        match std::fs::remove_file(self.manifest_path(refname)?) {
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            res => Ok(res?),
+        }
+    }
+}
+
+fn read_if_exists(path: &Path) -> Result<Option<Vec<u8>>> {
+    match std::fs::read(path) {
+        Ok(data) => Ok(Some(data)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Push `ref_specs` of `repo` to `store`, returning `true` if any objects were stored.
+///
+/// Ref specs are in the form used for git pushes, i.e. `+<rev>:<refname>` to update `refname`,
+/// and `:<refname>` to delete it.
+/// All objects are stored before the first ref is updated, so failing to store them leaves all
+/// refs untouched.
+pub fn push(repo: &git2::Repository, store: &dyn ObjectStore, ref_specs: &[&str]) -> Result<bool> {
+    let mut updates = Vec::with_capacity(ref_specs.len());
+    let mut objects_stored = 0;
+    for ref_spec in ref_specs {
+        let (src, dst) = ref_spec
+            .trim_start_matches('+')
+            .split_once(':')
+            .with_context(|| format!("invalid ref spec: '{ref_spec}'"))?;
+        if src.is_empty() {
+            updates.push((dst, None));
+            continue;
+        }
+        let target = repo
+            .revparse_single(src)
+            .with_context(|| format!("failed to find '{src}'"))?
+            .id();
+        objects_stored += store_closure(repo, store, target)?;
+        updates.push((dst, Some(target)));
+    }
+    for (dst, target) in updates {
+        match target {
+            Some(target) => store.write_manifest(dst, &RefManifest { target })?,
+            None => store.delete_manifest(dst)?,
+        }
+    }
+    Ok(objects_stored > 0)
+}
+
+/// Write `id` and everything it refers to into `store`, returning the amount of objects that were stored.
+fn store_closure(repo: &git2::Repository, store: &dyn ObjectStore, id: git2::Oid) -> Result<usize> {
+    let odb = repo.odb()?;
+    let mut stored = 0;
+    // Objects are written only once everything they refer to is stored.
+    let mut stack = vec![(id, false)];
+    while let Some((id, referents_stored)) = stack.pop() {
+        if store.contains(id)? {
+            continue;
+        }
+        let object = odb.read(id)?;
+        if referents_stored {
+            store.write(id, object.kind(), object.data())?;
+            stored += 1;
+            continue;
+        }
+        stack.push((id, true));
+        for referent in referents(repo, id, object.kind())? {
+            stack.push((referent, false));
+        }
+    }
+    Ok(stored)
+}
+
+/// Return the ids of all objects the object `id` of `kind` refers to.
+fn referents(
+    repo: &git2::Repository,
+    id: git2::Oid,
+    kind: git2::ObjectType,
+) -> Result<Vec<git2::Oid>> {
+    Ok(match kind {
+        git2::ObjectType::Commit => {
+            let commit = repo.find_commit(id)?;
+            std::iter::once(commit.tree_id())
+                .chain(commit.parent_ids())
+                .collect()
+        }
+        git2::ObjectType::Tree => repo
+            .find_tree(id)?
+            .iter()
+            // Submodule commits aren't part of this repository.
+            .filter(|entry| entry.kind() != Some(git2::ObjectType::Commit))
+            .map(|entry| entry.id())
+            .collect(),
+        git2::ObjectType::Tag => vec![repo.find_tag(id)?.target_id()],
+        _ => Vec::new(),
+    })
+}
+
+/// Fetch the ref named `refname` from `store` into `repo`, writing all objects `repo` doesn't have yet,
+/// and return its target, or `None` if `store` doesn't know the ref.
+///
+/// Note that `refname` itself isn't updated in `repo`.
+pub fn fetch(
+    repo: &git2::Repository,
+    store: &dyn ObjectStore,
+    refname: &str,
+) -> Result<Option<git2::Oid>> {
+    let Some(manifest) = store.read_manifest(refname)? else {
+        return Ok(None);
+    };
+    let odb = repo.odb()?;
+    let mut stack = vec![manifest.target];
+    while let Some(id) = stack.pop() {
+        if odb.exists(id) {
+            continue;
+        }
+        let (kind, data) = store
+            .read(id)?
+            .with_context(|| format!("object {id} referenced by '{refname}' is missing"))?;
+        let written = odb.write(kind, &data)?;
+        if written != id {
+            bail!("object {id} is corrupted as its content hashes to {written}");
+        }
+        stack.extend(referents(repo, id, kind)?);
+    }
+    Ok(Some(manifest.target))
+}
@@ -0,0 +1,98 @@
+//! Coalescing the writes of the push state while pushing the batches of the target history, as
+//! writing it after each batch slows down pushes of long histories on slow disks.
+use std::time::{Duration, Instant};
+
+/// Write the push state at least after this many batches.
+pub(crate) const MAX_BATCHES: usize = 10;
+
+/// Write the push state at least after this much time passed since the last write.
+pub(crate) const MAX_DELAY: Duration = Duration::from_secs(5);
+
+/// Tracks the last pushed batch to decide when its state should be written.
+///
+/// Only commits of batches that were pushed are ever returned, so the written state may be behind
+/// what the server has after a crash, but never ahead of it.
+pub(crate) struct Checkpoints {
+    /// The commit ending the last pushed batch, if its state wasn't written yet.
+    pending: Option<git2::Oid>,
+    /// The amount of batches pushed since the last write.
+    batches: usize,
+    /// When the state was last written, or when tracking started.
+    last_write: Instant,
+}
+
+impl Checkpoints {
+    /// Start tracking batches at `now`.
+    pub(crate) fn new(now: Instant) -> Self {
+        Checkpoints {
+            pending: None,
+            batches: 0,
+            last_write: now,
+        }
+    }
+
+    /// Record that the batch ending with `id` was pushed at `now`, and return the commit whose
+    /// state should be written now, if any.
+    pub(crate) fn pushed(&mut self, id: git2::Oid, now: Instant) -> Option<git2::Oid> {
+        self.pending = Some(id);
+        self.batches += 1;
+        if self.batches >= MAX_BATCHES || now.duration_since(self.last_write) >= MAX_DELAY {
+            self.batches = 0;
+            self.last_write = now;
+            self.pending.take()
+        } else {
+            None
+        }
+    }
+
+    /// Return the commit of the last pushed batch whose state wasn't written yet, to write it
+    /// before the push ends.
+    pub(crate) fn take_pending(&mut self) -> Option<git2::Oid> {
+        self.batches = 0;
+        self.pending.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn oid(n: u8) -> git2::Oid {
+        git2::Oid::from_bytes(&[n; 20]).unwrap()
+    }
+
+    #[test]
+    fn writes_are_coalesced_by_batches() {
+        let now = Instant::now();
+        let mut checkpoints = Checkpoints::new(now);
+        for n in 1..MAX_BATCHES {
+            assert_eq!(checkpoints.pushed(oid(n as u8), now), None);
+        }
+        assert_eq!(
+            checkpoints.pushed(oid(MAX_BATCHES as u8), now),
+            Some(oid(MAX_BATCHES as u8)),
+            "the last pushed batch is written"
+        );
+        assert_eq!(checkpoints.take_pending(), None, "nothing is left to write");
+    }
+
+    #[test]
+    fn writes_are_coalesced_by_time() {
+        let now = Instant::now();
+        let mut checkpoints = Checkpoints::new(now);
+        assert_eq!(checkpoints.pushed(oid(1), now), None);
+        assert_eq!(checkpoints.pushed(oid(2), now + MAX_DELAY), Some(oid(2)));
+        assert_eq!(checkpoints.pushed(oid(3), now + MAX_DELAY), None);
+    }
+
+    #[test]
+    fn the_last_pushed_batch_is_pending() {
+        let now = Instant::now();
+        let mut checkpoints = Checkpoints::new(now);
+        assert_eq!(checkpoints.take_pending(), None);
+        checkpoints.pushed(oid(1), now);
+        checkpoints.pushed(oid(2), now);
+        assert_eq!(checkpoints.take_pending(), Some(oid(2)));
+        assert_eq!(checkpoints.take_pending(), None);
+    }
+}
@@ -0,0 +1,166 @@
+//! The sync settings of a project, extracted into a type of its own so they can be compared
+//! and copied from one project to another, i.e. to use a project as template.
+
+use anyhow::{anyhow, Context, Result};
+use gitbutler_error::error::Code;
+use gitbutler_project as projects;
+use gitbutler_project::{CodeSyncMode, Project, ProjectId, RefRetention};
+use serde::{Deserialize, Serialize};
+
+use crate::{cloud, history};
+
+/// All settings that affect how a project is synchronized with GitButler.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncConfig {
+    /// Whether the operations log is synced.
+    pub oplog: bool,
+    /// Whether the project code is synced.
+    pub code: bool,
+    /// Which refs are pushed when syncing code.
+    pub code_sync_mode: CodeSyncMode,
+    /// Whether submodule commits are pushed when syncing code.
+    pub push_submodules: bool,
+    /// Which refs are too old to be pushed when syncing code.
+    pub ref_retention: RefRetention,
+    /// Glob patterns of full ref names which aren't pushed when syncing code.
+    #[serde(default)]
+    pub code_push_excluded_refs: Vec<String>,
+    /// The most commits pushed at once when syncing code, or `None` to use the default.
+    #[serde(default)]
+    pub code_push_batch_size: Option<usize>,
+    /// The least amount of seconds between two code pushes, or `None` to push every time.
+    #[serde(default)]
+    pub code_push_min_interval_seconds: Option<u64>,
+}
+
+/// Identifies a single setting of a [`SyncConfig`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SyncSetting {
+    Oplog,
+    Code,
+    CodeSyncMode,
+    PushSubmodules,
+    RefRetention,
+    CodePushExcludedRefs,
+    CodePushBatchSize,
+    CodePushMinInterval,
+}
+
+impl SyncConfig {
+    /// Extract the sync configuration of `project`.
+    ///
+    /// Projects that aren't linked to GitButler don't sync anything.
+    pub fn from_project(project: &Project) -> Self {
+        let api = project.api.as_ref();
+        SyncConfig {
+            oplog: api.is_some_and(|api| api.sync),
+            code: api.is_some_and(|api| api.sync_code),
+            code_sync_mode: project.code_sync_mode,
+            push_submodules: project.push_submodules,
+            ref_retention: project.ref_retention,
+            code_push_excluded_refs: project.code_push_excluded_refs.clone(),
+            code_push_batch_size: project.code_push_batch_size,
+            code_push_min_interval_seconds: project.code_push_min_interval_seconds,
+        }
+    }
+
+    /// Return all settings which differ between `self` and `other`.
+    pub fn diff(&self, other: &SyncConfig) -> Vec<SyncSetting> {
+        let mut changed = Vec::new();
+        if self.oplog != other.oplog {
+            changed.push(SyncSetting::Oplog);
+        }
+        if self.code != other.code {
+            changed.push(SyncSetting::Code);
+        }
+        if self.code_sync_mode != other.code_sync_mode {
+            changed.push(SyncSetting::CodeSyncMode);
+        }
+        if self.push_submodules != other.push_submodules {
+            changed.push(SyncSetting::PushSubmodules);
+        }
+        if self.ref_retention != other.ref_retention {
+            changed.push(SyncSetting::RefRetention);
+        }
+        if self.code_push_excluded_refs != other.code_push_excluded_refs {
+            changed.push(SyncSetting::CodePushExcludedRefs);
+        }
+        if self.code_push_batch_size != other.code_push_batch_size {
+            changed.push(SyncSetting::CodePushBatchSize);
+        }
+        if self.code_push_min_interval_seconds != other.code_push_min_interval_seconds {
+            changed.push(SyncSetting::CodePushMinInterval);
+        }
+        changed
+    }
+
+    /// Assure this configuration can be applied to `project`.
+    pub fn validate(&self, project: &Project) -> Result<()> {
+        if (self.oplog || self.code) && project.api.is_none() {
+            return Err(anyhow!(
+                "Cannot enable sync for project '{}' as it isn't linked to GitButler",
+                project.title
+            )
+            .context(Code::Validation));
+        }
+        if self.code && !project.has_code_url() {
+            return Err(anyhow!(
+                "Cannot enable code sync for project '{}' as it has no code URL",
+                project.title
+            )
+            .context(Code::Validation));
+        }
+        if let Some(batch_size) = self
+            .code_push_batch_size
+            .filter(|size| !(1..=history::MAX_BATCH_SIZE).contains(size))
+        {
+            return Err(anyhow!(
+                "Cannot push batches of {batch_size} commits for project '{}', which must be between 1 and {}",
+                project.title,
+                history::MAX_BATCH_SIZE
+            )
+            .context(Code::Validation));
+        }
+        cloud::excluded_ref_patterns(&self.code_push_excluded_refs).context(Code::Validation)?;
+        Ok(())
+    }
+}
+
+/// Write `config` to the project identified by `project_id` after validating it.
+///
+/// Returns the settings that were changed in the process.
+pub fn apply_sync_config(
+    projects: &projects::Controller,
+    project_id: ProjectId,
+    config: &SyncConfig,
+) -> Result<Vec<SyncSetting>> {
+    let project = projects.get(project_id)?;
+    config.validate(&project)?;
+
+    let changed = SyncConfig::from_project(&project).diff(config);
+    if changed.is_empty() {
+        return Ok(changed);
+    }
+
+    let api = project.api.clone().map(|mut api| {
+        api.sync = config.oplog;
+        api.sync_code = config.code;
+        api
+    });
+    projects.update(&projects::UpdateRequest {
+        id: project_id,
+        api,
+        code_sync_mode: Some(config.code_sync_mode),
+        push_submodules: Some(config.push_submodules),
+        ref_retention: Some(config.ref_retention),
+        code_push_excluded_refs: Some(config.code_push_excluded_refs.clone()),
+        code_push_batch_size: config.code_push_batch_size,
+        unset_code_push_batch_size: config.code_push_batch_size.is_none(),
+        code_push_min_interval_seconds: config.code_push_min_interval_seconds,
+        unset_code_push_min_interval_seconds: config.code_push_min_interval_seconds.is_none(),
+        ..Default::default()
+    })?;
+    Ok(changed)
+}
@@ -0,0 +1,77 @@
+//! Giving up on pushes and fetches that take too long, as `libgit2` keeps waiting for servers that
+//! hold a connection open without finishing them.
+use std::{
+    sync::mpsc::{self, RecvTimeoutError},
+    time::Duration,
+};
+
+use anyhow::{anyhow, Result};
+
+use crate::cloud::RemoteError;
+
+/// Run `work` on a thread of its own and return its result, or fail with [`RemoteError::Network`]
+/// if it didn't finish within `timeout`, so the failure is retried like any other network failure.
+///
+/// An unfinished `work` can't be stopped, so it's left to end on its own, which for a push or fetch
+/// is when the server closes the connection or the app quits. Its result is dropped then.
+pub(crate) fn run<T: Send + 'static>(
+    timeout: Duration,
+    work: impl FnOnce() -> Result<T> + Send + 'static,
+) -> Result<T> {
+    let (tx, rx) = mpsc::sync_channel(1);
+    std::thread::Builder::new()
+        .name("gitbutler-remote".into())
+        .spawn(move || {
+            // The receiver is gone if the deadline passed.
+            tx.send(work()).ok();
+        })?;
+    match rx.recv_timeout(timeout) {
+        Ok(res) => res,
+        Err(RecvTimeoutError::Timeout) => Err(RemoteError::Network(git2::Error::new(
+            git2::ErrorCode::GenericError,
+            git2::ErrorClass::Net,
+            format!("the server didn't respond within {timeout:?}"),
+        ))
+        .into()),
+        Err(RecvTimeoutError::Disconnected) => {
+            Err(anyhow!("the remote operation ended without result"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+
+    #[test]
+    fn work_past_the_deadline_is_a_network_failure() {
+        let started = Instant::now();
+        let err = run(Duration::from_millis(50), || {
+            std::thread::sleep(Duration::from_secs(10));
+            Ok(())
+        })
+        .unwrap_err();
+        assert!(
+            matches!(
+                err.downcast_ref::<RemoteError>(),
+                Some(RemoteError::Network(_))
+            ),
+            "{err:?}"
+        );
+        assert!(
+            started.elapsed() < Duration::from_secs(5),
+            "it doesn't wait for the work to end"
+        );
+    }
+
+    #[test]
+    fn work_within_the_deadline_returns_its_result() {
+        assert_eq!(run(Duration::from_secs(10), || Ok(42)).unwrap(), 42);
+        assert!(run(Duration::from_secs(10), || -> Result<()> {
+            Err(anyhow!("failed"))
+        })
+        .is_err());
+    }
+}
@@ -0,0 +1,77 @@
+//! Collapsing bursts of push requests, like those of saving many files at once, into a single push.
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    time::{Duration, Instant},
+};
+
+use but_settings::app_settings::CodePushSettings;
+
+/// Holds back push requests of projects identified by `K` until no more came in for a while.
+///
+/// A request is due once no other request for the same project came in for the `window`,
+/// or once the `max_delay` passed since the first one that is still held back, so projects
+/// that keep changing are still pushed regularly.
+///
+/// This doesn't keep time itself, so callers pass the current time and wait until the
+/// [next due request](PushDebounce::next_due).
+#[derive(Debug)]
+pub struct PushDebounce<K> {
+    window: Duration,
+    max_delay: Duration,
+    /// The first and the last held back request of each project.
+    pending: HashMap<K, (Instant, Instant)>,
+}
+
+impl<K: Hash + Eq + Clone> PushDebounce<K> {
+    /// Hold back requests until none came in for `window`, but no longer than `max_delay`.
+    pub fn new(window: Duration, max_delay: Duration) -> Self {
+        PushDebounce {
+            window,
+            max_delay,
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Hold back requests as configured in `settings`.
+    pub fn from_settings(settings: &CodePushSettings) -> Self {
+        Self::new(
+            Duration::from_millis(settings.push_debounce_millis),
+            Duration::from_secs(settings.push_debounce_max_seconds),
+        )
+    }
+
+    /// Record a request to push the project `key` at `now`.
+    pub fn request(&mut self, key: K, now: Instant) {
+        self.pending
+            .entry(key)
+            .and_modify(|(_first, last)| *last = now)
+            .or_insert((now, now));
+    }
+
+    /// Return when the earliest held back request is due, or `None` if there are none.
+    pub fn next_due(&self) -> Option<Instant> {
+        self.pending
+            .values()
+            .map(|requests| self.due(requests))
+            .min()
+    }
+
+    /// Return the projects whose requests are due at `now`, which are no longer held back then.
+    pub fn take_due(&mut self, now: Instant) -> Vec<K> {
+        let due: Vec<_> = self
+            .pending
+            .iter()
+            .filter(|(_key, requests)| self.due(requests) <= now)
+            .map(|(key, _requests)| key.clone())
+            .collect();
+        for key in &due {
+            self.pending.remove(key);
+        }
+        due
+    }
+
+    fn due(&self, (first, last): &(Instant, Instant)) -> Instant {
+        (*last + self.window).min(*first + self.max_delay)
+    }
+}
@@ -0,0 +1,63 @@
+//! Checking for free disk space before first pushes, which may need a lot of it for packing.
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use but_settings::app_settings::CodePushSettings;
+
+use crate::events::Event;
+
+/// Provides the amount of free space on disks.
+pub trait DiskSpace {
+    /// Return the amount of bytes available on the disk that holds `path`.
+    fn available_bytes(&self, path: &Path) -> Result<u64>;
+}
+
+/// The free disk space as reported by the operating system.
+pub struct SystemDiskSpace;
+
+impl DiskSpace for SystemDiskSpace {
+    fn available_bytes(&self, path: &Path) -> Result<u64> {
+        let path = gix::path::realpath(path)?;
+        let disks = sysinfo::Disks::new_with_refreshed_list();
+        disks
+            .iter()
+            .filter(|disk| path.starts_with(disk.mount_point()))
+            .max_by_key(|disk| disk.mount_point().as_os_str().len())
+            .map(|disk| disk.available_space())
+            .with_context(|| format!("could not find the disk holding '{}'", path.display()))
+    }
+}
+
+/// Estimate how many bytes a first push of `repo` needs, assuming all of its objects are sent.
+pub fn estimate_first_push_bytes(repo: &git2::Repository) -> Result<u64> {
+    let mut bytes = 0;
+    let mut dirs = vec![repo.path().join("objects")];
+    while let Some(dir) = dirs.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            if metadata.is_dir() {
+                dirs.push(entry.path());
+            } else {
+                bytes += metadata.len();
+            }
+        }
+    }
+    Ok(bytes)
+}
+
+/// Return [`Event::SkippedLowDisk`] if a first push of `repo` is large according to `settings`,
+/// and `disk` doesn't have enough space for it.
+pub fn check_first_push(
+    repo: &git2::Repository,
+    settings: &CodePushSettings,
+    disk: &dyn DiskSpace,
+) -> Result<Option<Event>> {
+    let estimate = estimate_first_push_bytes(repo)?;
+    if estimate < settings.disk_check_threshold_bytes {
+        return Ok(None);
+    }
+    let needed = estimate.saturating_add(settings.disk_safety_margin_bytes);
+    let available = disk.available_bytes(repo.path())?;
+    Ok((available < needed).then_some(Event::SkippedLowDisk { needed, available }))
+}
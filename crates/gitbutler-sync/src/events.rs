@@ -0,0 +1,116 @@
+//! Events emitted while pushing code to GitButler, to inform the frontend about what's happening.
+
+use std::path::PathBuf;
+
+use gitbutler_reference::Refname;
+use serde::Serialize;
+
+/// Something the frontend should know about a code push.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum Event {
+    /// The push entered a new phase.
+    Phase(PushPhase),
+    /// Nothing was pushed as GitButler wasn't set up for the project yet, i.e. it has no base branch.
+    ///
+    /// This resolves itself once a base branch is configured.
+    SkippedGbRepoUninitialized,
+    /// Nothing was pushed as the first push is estimated to need `needed` bytes of disk space,
+    /// including a safety margin, but only `available` bytes are free.
+    SkippedLowDisk { needed: u64, available: u64 },
+    /// Nothing was pushed as the last push was less than the minimum interval of the project ago,
+    /// and the target didn't move since.
+    ///
+    /// The next push after the interval goes through.
+    SkippedTooSoon,
+    /// The target history was walked, and `commits` are about to be pushed in about `batches` batches.
+    ///
+    /// The amount of batches grows if batches are made smaller during the push.
+    PushStarted { commits: usize, batches: usize },
+    /// `completed` of `total` batches of the target history are pushed.
+    BatchPushed { completed: usize, total: usize },
+    /// `done` of `total` of the target history are pushed, in estimated objects, which follows what
+    /// is transferred more closely than the batches. If objects couldn't be counted, it's in batches,
+    /// whose total is an estimate that may grow.
+    ///
+    /// This follows each [`Event::BatchPushed`], and `done` reaches `total` with the last batch.
+    HistoryProgress { done: u64, total: u64 },
+    /// A single push to the server sent `objects` taking up `bytes`.
+    Transferred { objects: u64, bytes: u64 },
+    /// Pushing branches made the server point the `updated` refs to where they are locally, and
+    /// remove the `deleted` ones. Refs the server already had as they are aren't included.
+    ///
+    /// A push that pauses emits this for each part of the refs it pushed.
+    RefsPushed {
+        updated: Vec<Refname>,
+        deleted: Vec<Refname>,
+    },
+    /// Pushing the target history ended after `completed` of about `total` batches, no matter if it
+    /// completed, paused or failed. `last_pushed` is the commit ending the last batch that was pushed,
+    /// which the stored [`CodePushState`](gitbutler_project::CodePushState) points to by now,
+    /// unless no batch was pushed.
+    ///
+    /// The next push resumes after `last_pushed`.
+    HistoryPushEnded {
+        completed: usize,
+        total: usize,
+        #[serde(with = "gitbutler_serde::oid_opt")]
+        last_pushed: Option<git2::Oid>,
+    },
+    /// The memory used after pushing a batch of the target history, along with the most used during the push.
+    ///
+    /// Once it approaches the configured ceiling, the following batches are made smaller.
+    MemoryUsage { current_bytes: u64, peak_bytes: u64 },
+    /// Nothing was pushed as the server doesn't allow the user to write to the project.
+    ///
+    /// This is checked before the first push so it doesn't fail only after uploading a lot of history.
+    Forbidden,
+    /// The server reported how much storage the project uses, out of `total_bytes` it may use.
+    QuotaUpdated { used_bytes: u64, total_bytes: u64 },
+    /// The push stopped early as it used up its budget, and resumes where it left off with the next push.
+    Paused,
+    /// The push stopped early as it was cancelled, e.g. as the project was closed, and resumes where it
+    /// left off with the next push.
+    Cancelled,
+    /// The push failed as the server couldn't be reached for `reason`, even after retrying, and is
+    /// tried again with the next push.
+    ///
+    /// Unlike other failures, this usually resolves itself once the network is back.
+    SyncDeferred { reason: String },
+    /// The push to the [mirror](gitbutler_project::CodePushMirror) at `url` ended, successfully
+    /// unless there is an `error`. Mirrors are pushed after the GitButler server, even if that failed.
+    MirrorPushed { url: String, error: Option<String> },
+    /// A dry run found that the push would send `ref_specs` to the server at once.
+    WouldPush { ref_specs: Vec<String> },
+    /// The submodule at `path` is checked out at `sha`, a commit which neither was pushed to GitButler
+    /// nor is known to be on any of the submodule's remotes.
+    ///
+    /// Anyone restoring the project from GitButler won't be able to check out the submodule.
+    UnsyncedSubmoduleCommit {
+        path: PathBuf,
+        #[serde(with = "gitbutler_serde::oid")]
+        sha: git2::Oid,
+    },
+}
+
+/// The phases a code push goes through, in order.
+///
+/// Phases may be skipped, e.g. if there is no history to push.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase", tag = "phase")]
+pub enum PushPhase {
+    /// Reading the target branch from the GitButler state.
+    ReadingTarget,
+    /// Finding the commits of the target branch that still have to be pushed, split into batches.
+    WalkingHistory,
+    /// Pushing one of the `batches` of the target history, with `batch` starting at 1.
+    PushingHistory { batch: usize, batches: usize },
+    /// Pointing the server-side ref of the project to the target.
+    PushingTarget,
+    /// Pushing all branches.
+    PushingRefs,
+    /// Pushing the commits the submodules are checked out at.
+    PushingSubmodules,
+    /// The push is complete.
+    Done,
+}
@@ -0,0 +1,351 @@
+//! Splitting the target history into the batches it's pushed in.
+use anyhow::Result;
+use gitbutler_command_context::CommandContext;
+use gitbutler_oxidize::git2_to_gix_object_id;
+
+use crate::cloud::rev_walk;
+
+/// The most commits a batch may have, so a single push never has to send most of a large history.
+pub(crate) const MAX_BATCH_SIZE: usize = 10_000;
+
+/// Return `size` as a usable batch size, with at least 1 and at most [`MAX_BATCH_SIZE`] commits,
+/// warning if it had to be changed.
+pub(crate) fn clamp_batch_size(size: usize) -> usize {
+    let clamped = size.clamp(1, MAX_BATCH_SIZE);
+    if clamped != size {
+        tracing::warn!(
+            size,
+            clamped,
+            "batch size is out of range, using the closest valid one"
+        );
+    }
+    clamped
+}
+
+/// Walk the commits in the history of `from` but not in the history of `until`, using the
+/// generation numbers of the commit-graph of the repository of `ctx`, if it has one.
+pub(crate) fn walk(
+    ctx: &CommandContext,
+    from: git2::Oid,
+    until: Option<git2::Oid>,
+) -> Result<History> {
+    let commits = rev_walk(ctx.repo(), from, until)?;
+    let graph = ctx.gix_repository()?.commit_graph_if_enabled()?;
+    Ok(History::new(commits, graph.as_ref()))
+}
+
+/// The commits of the target history that still have to be pushed, ready to be split into batches.
+///
+/// If generation numbers are known for all commits, batches are ranges of generations: with a
+/// batch size of `n`, generations `1..=n` form the first range, `n+1..=2n` the second, and so on.
+/// Each batch ends with the commit of the highest generation in its range, with the larger commit id
+/// winning ties. As generation numbers are a property of the history itself, the same history
+/// always yields the same batch boundaries, no matter the order in which it was walked, and even if
+/// the walk starts at a different commit of the same history.
+///
+/// A history with many merges can have more than `n` commits in a range, which is then split into
+/// batches of `n` commits, oldest first, in the same order. A batch pushes the history of the commit
+/// ending it though, so it also sends commits of side branches that earlier batches didn't contain.
+///
+/// Otherwise, commits are split into batches of `n` commits in the order they were walked.
+pub(crate) struct History {
+    /// The commits, newest first, so the last ones are pushed first.
+    commits: Vec<git2::Oid>,
+    /// The generation of each commit in `commits`, which are then sorted by it.
+    generations: Option<Vec<u32>>,
+}
+
+impl History {
+    /// Create a new instance from `commits` as walked from the target, newest first, using
+    /// the generation numbers of `graph` if it knows all commits.
+    pub(crate) fn new(
+        mut commits: Vec<git2::Oid>,
+        graph: Option<&gix::commitgraph::Graph>,
+    ) -> Self {
+        let generations: Option<Vec<_>> = graph.and_then(|graph| {
+            commits
+                .iter()
+                .map(|id| {
+                    graph
+                        .commit_by_id(git2_to_gix_object_id(*id))
+                        .map(|commit| commit.generation())
+                })
+                .collect()
+        });
+        let generations = generations.map(|generations| {
+            let mut by_generation: Vec<_> = generations.into_iter().zip(commits).collect();
+            by_generation.sort_by(|a, b| b.cmp(a));
+            let (generations, sorted) = by_generation.into_iter().unzip();
+            commits = sorted;
+            generations
+        });
+        History {
+            commits,
+            generations,
+        }
+    }
+
+    pub(crate) fn commits(&self) -> &[git2::Oid] {
+        &self.commits
+    }
+
+    /// Return `true` if batches are based on generation numbers.
+    pub(crate) fn has_generations(&self) -> bool {
+        self.generations.is_some()
+    }
+
+    /// Return the index of the commit that ends the oldest batch of `size` within the first `end`
+    /// commits, which ends up being the first commit of the batch as commits are newest first.
+    ///
+    /// Batches are pushed oldest first, so the next batch is within the commits before that index.
+    pub(crate) fn batch_start(&self, end: usize, size: usize) -> usize {
+        let size = size.clamp(1, MAX_BATCH_SIZE);
+        match &self.generations {
+            Some(generations) if end > 0 => {
+                let size = u32::try_from(size).unwrap_or(u32::MAX);
+                let oldest = generations[end - 1];
+                let range_end = (oldest.saturating_sub(1) / size)
+                    .saturating_add(1)
+                    .saturating_mul(size);
+                let range_start =
+                    generations[..end].partition_point(|generation| *generation > range_end);
+                range_start.max(end.saturating_sub(size as usize))
+            }
+            _ => end.saturating_sub(size),
+        }
+    }
+
+    /// Return the amount of batches of `size` within the first `end` commits.
+    pub(crate) fn batches(&self, end: usize, size: usize) -> usize {
+        let size = clamp_batch_size(size);
+        let mut batches = 0;
+        let mut end = end;
+        while end > 0 {
+            end = self.batch_start(end, size);
+            batches += 1;
+        }
+        batches
+    }
+
+    /// Return the commit ending each batch of `size`, oldest batch first.
+    pub(crate) fn boundaries(&self, size: usize) -> Vec<git2::Oid> {
+        let size = clamp_batch_size(size);
+        let mut boundaries = Vec::new();
+        let mut end = self.commits.len();
+        while end > 0 {
+            let start = self.batch_start(end, size);
+            boundaries.push(self.commits[start]);
+            end = start;
+        }
+        boundaries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Create a linear history of `count` empty commits on top of the head of `repo`.
+    fn linear_history(repo: &git2::Repository, count: usize) {
+        let signature = git2::Signature::now("test", "test@example.com").unwrap();
+        for n in 0..count {
+            let head = repo.head().unwrap().peel_to_commit().unwrap();
+            repo.commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                &format!("commit {n}"),
+                &head.tree().unwrap(),
+                &[&head],
+            )
+            .unwrap();
+        }
+    }
+
+    fn walk(repo: &git2::Repository, from: git2::Oid, sorting: git2::Sort) -> Vec<git2::Oid> {
+        let mut revwalk = repo.revwalk().unwrap();
+        revwalk.set_sorting(sorting).unwrap();
+        revwalk.push(from).unwrap();
+        revwalk.map(Result::unwrap).collect()
+    }
+
+    #[test]
+    fn generation_boundaries_are_the_same_for_independent_walks() {
+        let (repo, _tmp) = gitbutler_testsupport::test_repository();
+        linear_history(&repo, 30);
+        let status = std::process::Command::new("git")
+            .args(["commit-graph", "write", "--reachable"])
+            .current_dir(repo.path())
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let gix_repo = gix::open(repo.path()).unwrap();
+        let graph = gix_repo.commit_graph_if_enabled().unwrap();
+        assert!(graph.is_some(), "the commit-graph was written");
+
+        let head = repo.head().unwrap().target().unwrap();
+        let newest_first = walk(&repo, head, git2::Sort::TOPOLOGICAL);
+        let oldest_first = walk(&repo, head, git2::Sort::TOPOLOGICAL | git2::Sort::REVERSE);
+        let a = History::new(newest_first.clone(), graph.as_ref());
+        let b = History::new(oldest_first, graph.as_ref());
+        assert!(a.has_generations());
+        assert_eq!(a.boundaries(12), b.boundaries(12));
+        assert_eq!(a.boundaries(12).last(), Some(&head), "the target is last");
+
+        // Every 12th generation ends a batch, no matter where the history is cut off.
+        let partial = History::new(newest_first[5..].to_vec(), graph.as_ref());
+        let full = a.boundaries(12);
+        assert_eq!(
+            partial.boundaries(12)[..full.len() - 1],
+            full[..full.len() - 1]
+        );
+    }
+
+    #[test]
+    fn every_batch_of_a_large_history_has_its_own_boundary() {
+        let (repo, _tmp) = gitbutler_testsupport::test_repository();
+        let base = repo.head().unwrap().target().unwrap();
+        linear_history(&repo, 2500);
+        let status = std::process::Command::new("git")
+            .args(["commit-graph", "write", "--reachable"])
+            .current_dir(repo.path())
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let gix_repo = gix::open(repo.path()).unwrap();
+        let graph = gix_repo.commit_graph_if_enabled().unwrap();
+
+        let head = repo.head().unwrap().target().unwrap();
+        let mut revwalk = repo.revwalk().unwrap();
+        revwalk.set_sorting(git2::Sort::TOPOLOGICAL).unwrap();
+        revwalk.push(head).unwrap();
+        revwalk.hide(base).unwrap();
+        let commits: Vec<_> = revwalk.map(Result::unwrap).collect();
+        assert_eq!(commits.len(), 2500);
+
+        for history in [
+            History::new(commits.clone(), None),
+            History::new(commits, graph.as_ref()),
+        ] {
+            let boundaries = history.boundaries(1000);
+            assert_eq!(boundaries.len(), 3, "2500 commits need 3 batches of 1000");
+            assert_eq!(
+                boundaries.last(),
+                Some(&head),
+                "the target ends the last batch"
+            );
+            for pair in boundaries.windows(2) {
+                assert!(
+                    repo.graph_descendant_of(pair[1], pair[0]).unwrap(),
+                    "each batch builds on the one before"
+                );
+                let (ahead, _) = repo.graph_ahead_behind(pair[1], pair[0]).unwrap();
+                assert!(ahead <= 1000, "no batch is larger than the batch size");
+            }
+        }
+    }
+
+    #[test]
+    fn generation_ranges_with_many_merges_are_split() {
+        let (repo, _tmp) = gitbutler_testsupport::test_repository();
+        let signature = git2::Signature::now("test", "test@example.com").unwrap();
+        // Each merge has 6 side commits of the same generation, so every range is crowded.
+        for merge in 0..4 {
+            let head = repo.head().unwrap().peel_to_commit().unwrap();
+            let tree = head.tree().unwrap();
+            let sides: Vec<_> = (0..6)
+                .map(|side| {
+                    let id = repo
+                        .commit(
+                            None,
+                            &signature,
+                            &signature,
+                            &format!("side {side} of merge {merge}"),
+                            &tree,
+                            &[&head],
+                        )
+                        .unwrap();
+                    repo.find_commit(id).unwrap()
+                })
+                .collect();
+            let parents: Vec<_> = sides.iter().collect();
+            repo.commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                &format!("merge {merge}"),
+                &tree,
+                &parents,
+            )
+            .unwrap();
+        }
+        let status = std::process::Command::new("git")
+            .args(["commit-graph", "write", "--reachable"])
+            .current_dir(repo.path())
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let gix_repo = gix::open(repo.path()).unwrap();
+        let graph = gix_repo.commit_graph_if_enabled().unwrap();
+
+        let head = repo.head().unwrap().target().unwrap();
+        let newest_first = walk(&repo, head, git2::Sort::TOPOLOGICAL);
+        let oldest_first = walk(&repo, head, git2::Sort::TOPOLOGICAL | git2::Sort::REVERSE);
+        let history = History::new(newest_first, graph.as_ref());
+        assert!(history.has_generations());
+
+        let mut end = history.commits().len();
+        while end > 0 {
+            let start = history.batch_start(end, 4);
+            assert!(end - start <= 4, "no batch holds more than the batch size");
+            end = start;
+        }
+        assert_eq!(
+            history.boundaries(4),
+            History::new(oldest_first, graph.as_ref()).boundaries(4),
+            "split ranges are the same for independent walks"
+        );
+        assert_eq!(history.boundaries(4).last(), Some(&head));
+    }
+
+    #[test]
+    fn counts_are_used_without_generations() {
+        let (repo, _tmp) = gitbutler_testsupport::test_repository();
+        linear_history(&repo, 4);
+        let head = repo.head().unwrap().target().unwrap();
+        let commits = walk(&repo, head, git2::Sort::TOPOLOGICAL);
+
+        let history = History::new(commits.clone(), None);
+        assert!(!history.has_generations());
+        assert_eq!(history.boundaries(2), vec![commits[3], commits[1]]);
+        assert_eq!(history.boundaries(10), vec![commits[0]]);
+    }
+
+    #[test]
+    fn out_of_range_batch_sizes_are_clamped() {
+        let (repo, _tmp) = gitbutler_testsupport::test_repository();
+        linear_history(&repo, 4);
+        let head = repo.head().unwrap().target().unwrap();
+        let commits = walk(&repo, head, git2::Sort::TOPOLOGICAL);
+        let oldest_first: Vec<_> = commits.iter().rev().copied().collect();
+
+        let history = History::new(commits.clone(), None);
+        assert_eq!(
+            history.boundaries(0),
+            oldest_first,
+            "empty batches are batches of 1"
+        );
+        assert_eq!(history.batches(commits.len(), 0), commits.len());
+        assert_eq!(history.boundaries(1), oldest_first);
+        assert_eq!(
+            history.boundaries(commits.len() + 1),
+            vec![head],
+            "a single batch ends with the target"
+        );
+        assert_eq!(history.boundaries(usize::MAX), vec![head]);
+
+        assert_eq!(clamp_batch_size(0), 1);
+        assert_eq!(clamp_batch_size(12), 12);
+        assert_eq!(clamp_batch_size(usize::MAX), MAX_BATCH_SIZE);
+    }
+}
@@ -0,0 +1,78 @@
+//! Finding out what the GitButler server has that isn't available locally, before it's fetched into
+//! the branches of the repository.
+use std::collections::HashSet;
+
+use anyhow::{Context, Result};
+use gitbutler_command_context::CommandContext;
+use gitbutler_user as users;
+use serde::Serialize;
+
+use crate::{
+    cloud::{mirror_server_refs, GITBUTLER_REMOTE, SERVER_REFS_NAMESPACE},
+    transport::{CodePushTransport, GitTransport},
+};
+
+/// A branch on the server with commits that aren't available locally.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InboundRef {
+    /// The full name of the branch on the server, like `refs/heads/main`.
+    pub name: String,
+    /// The commits of the branch that aren't in the history of any local ref, newest first.
+    ///
+    /// Refs fetched from the server don't count as local, so this lists what others pushed.
+    #[serde(with = "gitbutler_serde::oid_vec")]
+    pub commits: Vec<git2::Oid>,
+}
+
+/// Return the branches of the GitButler code remote, as seen by `user`, which have commits that
+/// aren't in the history of any ref of the repository of `ctx`, i.e. what a fetch would bring in.
+///
+/// Listing these commits needs them, so this downloads all objects of the branches on the server
+/// into the repository, with refs in [`SERVER_REFS_NAMESPACE`] that replace the ones of the last
+/// call. Other refs aren't changed, and neither are the branches fetched from the server into
+/// `refs/remotes/gitbutler`, which are ignored as local refs.
+///
+/// Branches without such commits are omitted, so the result is empty if the repository is in sync
+/// with the server. Failing to reach the server is a [`RemoteError::Network`](crate::cloud::RemoteError::Network).
+pub fn inbound_commits(ctx: &CommandContext, user: &users::User) -> Result<Vec<InboundRef>> {
+    inbound_commits_via(ctx, user, &GitTransport)
+}
+
+/// Like [`inbound_commits()`], but fetching from the server with `transport`.
+pub fn inbound_commits_via(
+    ctx: &CommandContext,
+    user: &users::User,
+    transport: &dyn CodePushTransport,
+) -> Result<Vec<InboundRef>> {
+    let server_refs = mirror_server_refs(ctx, user, transport, "refs/heads/*")?;
+    let repo = ctx.repo();
+    let fetched = format!("refs/remotes/{GITBUTLER_REMOTE}/");
+    let local_tips: HashSet<_> = repo
+        .references()?
+        .filter_map(Result::ok)
+        .filter(|reference| {
+            reference.name().is_some_and(|name| {
+                !name.starts_with(SERVER_REFS_NAMESPACE) && !name.starts_with(&fetched)
+            })
+        })
+        .filter_map(|reference| reference.peel_to_commit().ok().map(|commit| commit.id()))
+        .collect();
+
+    let mut inbound = Vec::new();
+    for (name, target) in server_refs {
+        let mut revwalk = repo.revwalk().context("failed to create revwalk")?;
+        revwalk.push(target)?;
+        for tip in &local_tips {
+            revwalk.hide(*tip)?;
+        }
+        let commits = revwalk
+            .collect::<Result<Vec<_>, _>>()
+            .context("failed to get oid")?;
+        if !commits.is_empty() {
+            inbound.push(InboundRef { name, commits });
+        }
+    }
+    inbound.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(inbound)
+}
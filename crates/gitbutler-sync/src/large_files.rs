@@ -0,0 +1,82 @@
+//! Finding files in the target history that are too large to be pushed, before pushing any of it.
+use std::{collections::BTreeMap, path::PathBuf};
+
+use anyhow::Result;
+
+/// A file of the history to push that is larger than allowed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct LargeFile {
+    /// The path of the file relative to the root of the tree.
+    pub(crate) path: PathBuf,
+    /// The size of the largest version of the file.
+    pub(crate) bytes: u64,
+    /// The commit that introduced the largest version of the file.
+    pub(crate) commit: git2::Oid,
+}
+
+/// Return the files larger than `max_bytes` that `commits` of `repo` add or change, ordered by path.
+///
+/// Only the changes of each commit relative to its parents are looked at, as all files of the
+/// parents are either in `commits` as well, or were pushed already.
+pub(crate) fn find(
+    repo: &git2::Repository,
+    commits: &[git2::Oid],
+    max_bytes: u64,
+) -> Result<Vec<LargeFile>> {
+    let odb = repo.odb()?;
+    let mut large = BTreeMap::<PathBuf, LargeFile>::new();
+    for id in commits {
+        let commit = repo.find_commit(*id)?;
+        let tree = commit.tree()?;
+        let parents = commit
+            .parents()
+            .map(|parent| parent.tree())
+            .collect::<Result<Vec<_>, _>>()?;
+        let bases: Vec<_> = if parents.is_empty() {
+            vec![None]
+        } else {
+            parents.iter().map(Some).collect()
+        };
+        // Files of merges are only new if none of the parents has them.
+        let mut candidates: Option<BTreeMap<PathBuf, (u64, git2::Oid)>> = None;
+        for base in bases {
+            let diff = repo.diff_tree_to_tree(base, Some(&tree), None)?;
+            let mut files = BTreeMap::new();
+            for delta in diff.deltas() {
+                let file = delta.new_file();
+                let is_blob = matches!(
+                    file.mode(),
+                    git2::FileMode::Blob | git2::FileMode::BlobExecutable
+                );
+                let Some(path) = file.path().filter(|_| is_blob && !file.id().is_zero()) else {
+                    continue;
+                };
+                let (bytes, _kind) = odb.read_header(file.id())?;
+                let bytes = bytes as u64;
+                if bytes > max_bytes {
+                    files.insert(path.to_owned(), (bytes, file.id()));
+                }
+            }
+            candidates = Some(match candidates {
+                None => files,
+                Some(candidates) => candidates
+                    .into_iter()
+                    .filter(|(path, blob)| files.get(path) == Some(blob))
+                    .collect(),
+            });
+        }
+        for (path, (bytes, _blob)) in candidates.unwrap_or_default() {
+            if large.get(&path).is_none_or(|file| file.bytes < bytes) {
+                large.insert(
+                    path.clone(),
+                    LargeFile {
+                        path,
+                        bytes,
+                        commit: *id,
+                    },
+                );
+            }
+        }
+    }
+    Ok(large.into_values().collect())
+}
@@ -1,2 +1,26 @@
+mod abbrev;
+mod anchor;
+pub mod budget;
+pub mod cas;
+mod checkpoint;
 pub mod cloud;
+pub mod config;
+mod deadline;
+pub mod debounce;
+pub mod disk;
+pub mod events;
+mod history;
+pub mod inbound;
+mod large_files;
+pub mod memory;
+mod mirror;
+pub mod plan;
+mod progress;
+mod pushed_refs;
+pub mod queue;
+pub mod quota;
+pub mod reconcile;
+mod retry;
 pub mod stack_upload;
+mod throttle;
+pub mod transport;
@@ -0,0 +1,85 @@
+//! Observing the memory used while pushing, to keep pack generation of large pushes from exhausting it.
+use anyhow::{anyhow, Context, Result};
+
+use crate::events::Event;
+
+/// Provides the amount of memory used by the application.
+pub trait MemoryUsage {
+    /// Return the amount of bytes of memory in use.
+    fn used_bytes(&self) -> Result<u64>;
+}
+
+/// The memory used by the current process as reported by the operating system.
+pub struct ProcessMemory;
+
+impl MemoryUsage for ProcessMemory {
+    fn used_bytes(&self) -> Result<u64> {
+        let pid = sysinfo::get_current_pid()
+            .map_err(|err| anyhow!("could not determine the current process: {err}"))?;
+        let mut system = sysinfo::System::new();
+        system.refresh_processes_specifics(
+            sysinfo::ProcessesToUpdate::Some(&[pid]),
+            false,
+            sysinfo::ProcessRefreshKind::nothing().with_memory(),
+        );
+        system
+            .process(pid)
+            .map(|process| process.memory())
+            .context("could not find the current process")
+    }
+}
+
+/// The size of the batches of target history to push, which shrinks whenever the memory in use
+/// approaches a ceiling.
+///
+/// Packs are generated in memory, so smaller batches make for smaller peaks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AdaptiveBatchSize {
+    size: usize,
+    ceiling_bytes: Option<u64>,
+    peak_bytes: u64,
+}
+
+impl AdaptiveBatchSize {
+    /// Start with batches of `size` commits, and shrink them if more than three quarters of
+    /// `ceiling_bytes` are in use, where 0 means there is no ceiling.
+    pub fn new(size: usize, ceiling_bytes: u64) -> Self {
+        AdaptiveBatchSize {
+            size: size.max(1),
+            ceiling_bytes: (ceiling_bytes > 0).then_some(ceiling_bytes),
+            peak_bytes: 0,
+        }
+    }
+
+    /// The amount of commits the next batch should have.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// The most memory observed so far.
+    pub fn peak_bytes(&self) -> u64 {
+        self.peak_bytes
+    }
+
+    /// Account for `used_bytes` measured after pushing a batch, and return the [`Event::MemoryUsage`] to report.
+    pub fn observe(&mut self, used_bytes: u64) -> Event {
+        self.peak_bytes = self.peak_bytes.max(used_bytes);
+        if let Some(ceiling_bytes) = self.ceiling_bytes {
+            if used_bytes >= ceiling_bytes - ceiling_bytes / 4 && self.size > 1 {
+                let size = self.size / 2;
+                tracing::warn!(
+                    used_bytes,
+                    ceiling_bytes,
+                    previous_size = self.size,
+                    size,
+                    "memory is running low, pushing smaller batches",
+                );
+                self.size = size;
+            }
+        }
+        Event::MemoryUsage {
+            current_bytes: used_bytes,
+            peak_bytes: self.peak_bytes,
+        }
+    }
+}
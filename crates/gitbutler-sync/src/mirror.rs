@@ -0,0 +1,78 @@
+//! Pushing the project code to [mirrors](CodePushMirror) next to its GitButler server.
+//!
+//! A mirror is pushed by pushing a copy of the project that stands for it, see [`view()`], so
+//! everything that is stored about pushes is kept separately for each of them.
+use std::path::PathBuf;
+
+use gitbutler_project::{CodePushMirror, Project};
+
+/// Return a copy of `project` that pushes to `mirror`, with the push state of the mirror.
+pub(crate) fn view(project: &Project, mirror: &CodePushMirror) -> Project {
+    let mut view = project.clone();
+    if let Some(api) = view.api.as_mut() {
+        api.code_git_url = Some(mirror.url.clone());
+    }
+    view.gitbutler_code_push_state = mirror.state.clone();
+    view.code_push_mirrors = Vec::new();
+    view.code_push_mirror_url = Some(mirror.url.clone());
+    view
+}
+
+/// Return the path of the file `name` in which pushes of `project` keep their state, which is
+/// in a directory of its own for each mirror.
+pub(crate) fn state_path(project: &Project, name: &str) -> PathBuf {
+    match &project.code_push_mirror_url {
+        Some(url) => {
+            // The URL may contain characters that aren't valid in paths, unlike its hash.
+            let key = git2::Oid::hash_object(git2::ObjectType::Blob, url.as_bytes())
+                .expect("hashing bytes never fails");
+            project
+                .gb_dir()
+                .join("mirrors")
+                .join(key.to_string())
+                .join(name)
+        }
+        None => project.gb_dir().join(name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mirrors_keep_their_state_separately() {
+        let project = Project {
+            path: "/repo".into(),
+            code_push_mirrors: vec![
+                CodePushMirror {
+                    url: "https://backup.example.com/code.git".into(),
+                    state: None,
+                },
+                CodePushMirror {
+                    url: "https://other.example.com/code.git".into(),
+                    state: None,
+                },
+            ],
+            ..Default::default()
+        };
+        let mirrors: Vec<_> = project
+            .code_push_mirrors
+            .iter()
+            .map(|mirror| view(&project, mirror))
+            .collect();
+        assert_eq!(
+            state_path(&project, "anchor"),
+            project.gb_dir().join("anchor")
+        );
+        assert_ne!(
+            state_path(&mirrors[0], "anchor"),
+            state_path(&mirrors[1], "anchor")
+        );
+        assert_ne!(
+            state_path(&mirrors[0], "anchor"),
+            state_path(&project, "anchor")
+        );
+        assert!(mirrors[0].code_push_mirrors.is_empty());
+    }
+}
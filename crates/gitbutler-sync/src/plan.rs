@@ -0,0 +1,370 @@
+//! Splitting a code push into a plan, which can be inspected and approved before anything leaves
+//! the machine, and its execution.
+//!
+//! Plans are made from what a push would send, and executed by such a push once they are approved.
+//!
+//! Plans are saved while they are executed, so an execution that was interrupted, e.g. by a restart,
+//! can be resumed. How far it got is only known from the state of the last push, like for any other
+//! push, so the saved plan merely records what was approved.
+use std::{collections::HashSet, path::PathBuf};
+
+use anyhow::{anyhow, Result};
+use gitbutler_command_context::CommandContext;
+use gitbutler_error::error::Code;
+use gitbutler_project as projects;
+use gitbutler_project::{Project, ProjectId};
+use gitbutler_stack::VirtualBranchesHandle;
+use gitbutler_user as users;
+use serde::{Deserialize, Serialize};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    budget::PushOutcome,
+    cloud::{
+        batch_push, batch_size, history_to_push, load_pushed_refs, mirror_ref_spec,
+        push_unthrottled, refs_to_sync, target_pushed,
+    },
+    disk,
+    events::Event,
+    transport::CodePushTransport,
+};
+
+/// A ref that is pushed by a [`SyncPlan`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlannedRef {
+    /// The full name of the ref, like `refs/heads/main`.
+    pub name: String,
+    /// The object the ref points to, or `None` if it can't be resolved, which is pushed by name.
+    #[serde(with = "gitbutler_serde::oid_opt")]
+    pub target: Option<git2::Oid>,
+}
+
+/// Everything a code push of a project would send to the server.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncPlan {
+    /// The project the plan is for.
+    pub project_id: ProjectId,
+    /// The commit of the target branch, whose history is pushed first.
+    #[serde(with = "gitbutler_serde::oid")]
+    pub target: git2::Oid,
+    /// The commit of the target history that was pushed last, or `None` if nothing was pushed yet.
+    #[serde(with = "gitbutler_serde::oid_opt")]
+    pub last_pushed: Option<git2::Oid>,
+    /// The amount of commits of the target history that the server doesn't have yet.
+    pub commits: usize,
+    /// The newest commit of each batch of target history, in the order they are pushed.
+    ///
+    /// The target is pushed as last batch even if the server has its history, unless the last push
+    /// pushed it but not all refs.
+    #[serde(with = "gitbutler_serde::oid_vec")]
+    pub batches: Vec<git2::Oid>,
+    /// The refs to push, sorted by name, which leaves out those that didn't move since they were pushed.
+    pub refs: Vec<PlannedRef>,
+    /// The names of the refs to delete from the server, as they are gone or older than the retention period.
+    pub deleted_refs: Vec<String>,
+    /// The estimated amount of bytes to upload.
+    ///
+    /// For first pushes, that's the size of the object database, and otherwise the uncompressed
+    /// size of the new objects that aren't in the tree of the last pushed commit.
+    pub estimated_bytes: u64,
+}
+
+/// What [`resume()`] did.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum ResumeOutcome {
+    /// There was no interrupted execution.
+    NothingSaved,
+    /// The saved plan was executed, which may have ended before it completed, e.g. if the disk was
+    /// too full for a first push. It remains saved then.
+    Executed,
+    /// The saved plan was outdated and discarded, and this is the plan to approve instead.
+    Outdated(SyncPlan),
+}
+
+/// Plan the code push of the project of `ctx` without pushing anything.
+///
+/// The history and refs are picked just like a push does.
+pub fn create(ctx: &CommandContext) -> Result<SyncPlan> {
+    let project = ctx.project();
+    let repo = ctx.repo();
+    let target = VirtualBranchesHandle::new(project.gb_dir())
+        .get_default_target()?
+        .sha;
+    let previous = project.gitbutler_code_push_state.as_ref();
+    let last_pushed = previous.map(|state| state.id);
+    let (commits, batches) = if target_pushed(previous, target) {
+        (Vec::new(), Vec::new())
+    } else {
+        let history = history_to_push(ctx, target, previous)?;
+        let batches = if history.commits().is_empty() {
+            vec![target]
+        } else {
+            history.boundaries(batch_size(project))
+        };
+        (history.commits().to_vec(), batches)
+    };
+
+    let to_sync = refs_to_sync(ctx, previous, &load_pushed_refs(project)?, target)?;
+    let refs = to_sync
+        .updates
+        .into_iter()
+        .map(|(name, target)| PlannedRef { name, target })
+        .collect();
+
+    Ok(SyncPlan {
+        project_id: project.id,
+        target,
+        last_pushed,
+        commits: commits.len(),
+        batches,
+        refs,
+        deleted_refs: to_sync.deletions,
+        estimated_bytes: estimate_bytes(repo, &commits, last_pushed)?,
+    })
+}
+
+/// Find the ref-specs of each push a code push of the project of `ctx` would make, in order,
+/// without contacting the server or changing the project.
+///
+/// Each push is logged and reported to `on_event`, so the exact ref-specs can be attached to bug reports.
+/// Temporary batch refs, which are deleted once the target is pushed, aren't included, and neither
+/// are the pauses a push makes once its budget is used up.
+pub fn dry_run(ctx: &CommandContext, on_event: &dyn Fn(Event)) -> Result<Vec<Vec<String>>> {
+    let plan = create(ctx)?;
+    let project = ctx.project();
+    let batches = plan.batches.len();
+    let mut pushes: Vec<_> = plan
+        .batches
+        .iter()
+        .enumerate()
+        .map(|(idx, id)| {
+            let batch = idx + 1;
+            let target = (batch == batches).then_some(plan.target);
+            batch_push(project, *id, batch, batches, target).ref_specs
+        })
+        .collect();
+    // Like `push_all_refs()`, refs are pushed in chunks of the batch size, and deleted once all are pushed.
+    let chunk_size = batch_size(project);
+    pushes.extend(plan.refs.chunks(chunk_size).map(|refs| {
+        refs.iter()
+            .map(|r| mirror_ref_spec(project, &r.name, &r.name))
+            .collect()
+    }));
+    pushes.extend(
+        plan.deleted_refs
+            .chunks(chunk_size)
+            .map(|refs| refs.iter().map(|r| format!(":{r}")).collect()),
+    );
+
+    for ref_specs in &pushes {
+        tracing::info!(
+            project_id = %plan.project_id,
+            ?ref_specs,
+            "dry run would push",
+        );
+        on_event(Event::WouldPush {
+            ref_specs: ref_specs.clone(),
+        });
+    }
+    Ok(pushes)
+}
+
+/// Push exactly what `plan` describes with `transport`, informing about the progress via `on_event`.
+///
+/// The plan is rejected if it's outdated, i.e. if planning now would yield a different plan.
+/// It's pushed like any other push, just without waiting for the minimum interval between pushes.
+pub fn execute(
+    ctx: &CommandContext,
+    user: &users::User,
+    projects: &projects::Controller,
+    transport: &dyn CodePushTransport,
+    plan: &SyncPlan,
+    on_event: &dyn Fn(Event),
+) -> Result<PushOutcome> {
+    let project_id = ctx.project().id;
+    if plan.project_id != project_id {
+        return Err(anyhow!(
+            "The sync plan is for project '{}', not '{}'",
+            plan.project_id,
+            project_id
+        )
+        .context(Code::Validation));
+    }
+    if create(ctx)? != *plan {
+        return Err(anyhow!(
+            "The sync plan is outdated as the project changed since, please plan again"
+        )
+        .context(Code::Validation));
+    }
+    run(ctx, user, projects, transport, plan, on_event)
+}
+
+/// Return the plan whose execution was interrupted, if there is one.
+pub fn saved(project: &Project) -> Result<Option<SyncPlan>> {
+    match std::fs::read(saved_plan_path(project)) {
+        Ok(saved) => Ok(Some(serde_json::from_slice(&saved)?)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Continue the execution of the saved plan of the project of `ctx` with `transport`, if there is
+/// one, informing about the progress via `on_event`.
+///
+/// The saved plan is only executed if it still matches the project, i.e. the target didn't change,
+/// the last push is one of its batches or what it started from, and nothing but its refs is left
+/// to push. Otherwise it's discarded, and a new plan is returned instead, which has to be approved
+/// by executing it.
+pub fn resume(
+    ctx: &CommandContext,
+    user: &users::User,
+    projects: &projects::Controller,
+    transport: &dyn CodePushTransport,
+    on_event: &dyn Fn(Event),
+) -> Result<ResumeOutcome> {
+    let project = ctx.project();
+    let Some(saved) = saved(project)? else {
+        return Ok(ResumeOutcome::NothingSaved);
+    };
+    let current = create(ctx)?;
+    // The refs of the saved plan that were pushed already aren't planned anymore.
+    let Some(batches_pushed) = batches_pushed(project, &saved).filter(|_| {
+        saved.project_id == current.project_id
+            && saved.target == current.target
+            && current.refs.iter().all(|r| saved.refs.contains(r))
+            && current
+                .deleted_refs
+                .iter()
+                .all(|r| saved.deleted_refs.contains(r))
+    }) else {
+        tracing::info!(
+            project_id = %project.id,
+            "discarding outdated sync plan",
+        );
+        std::fs::remove_file(saved_plan_path(project))?;
+        return Ok(ResumeOutcome::Outdated(current));
+    };
+
+    tracing::info!(
+        project_id = %project.id,
+        batches_pushed,
+        "resuming sync plan",
+    );
+    run(ctx, user, projects, transport, &saved, on_event)?;
+    Ok(ResumeOutcome::Executed)
+}
+
+/// Return how many batches of `plan` were pushed according to the last push of `project`, or
+/// `None` if that isn't part of `plan`.
+fn batches_pushed(project: &Project, plan: &SyncPlan) -> Option<usize> {
+    let last_pushed = project
+        .gitbutler_code_push_state
+        .as_ref()
+        .map(|state| state.id);
+    if last_pushed == plan.last_pushed {
+        return Some(0);
+    }
+    plan.batches
+        .iter()
+        .position(|id| Some(*id) == last_pushed)
+        .map(|idx| idx + 1)
+}
+
+fn saved_plan_path(project: &Project) -> PathBuf {
+    project.gb_dir().join("sync_plan.json")
+}
+
+fn save(project: &Project, plan: &SyncPlan) -> Result<()> {
+    gitbutler_fs::write(saved_plan_path(project), serde_json::to_vec(plan)?)
+}
+
+/// Push the project of `ctx`, which `plan` was approved for, and keep the plan saved until the
+/// push completes so it can be resumed.
+///
+/// The push resumes from the state of the last push, like any other push.
+fn run(
+    ctx: &CommandContext,
+    user: &users::User,
+    projects: &projects::Controller,
+    transport: &dyn CodePushTransport,
+    plan: &SyncPlan,
+    on_event: &dyn Fn(Event),
+) -> Result<PushOutcome> {
+    let project = ctx.project();
+    save(project, plan)?;
+    let outcome = push_unthrottled(
+        ctx,
+        user,
+        projects,
+        transport,
+        &CancellationToken::new(),
+        on_event,
+    )?;
+    if outcome == PushOutcome::Complete {
+        std::fs::remove_file(saved_plan_path(project))?;
+        tracing::info!(
+            project_id = %project.id,
+            batches = plan.batches.len(),
+            refs = plan.refs.len(),
+            "sync plan executed",
+        );
+    }
+    Ok(outcome)
+}
+
+/// Estimate how many bytes pushing `commits` needs, given the server has `last_pushed` already.
+fn estimate_bytes(
+    repo: &git2::Repository,
+    commits: &[git2::Oid],
+    last_pushed: Option<git2::Oid>,
+) -> Result<u64> {
+    let Some(last_pushed) = last_pushed else {
+        return disk::estimate_first_push_bytes(repo);
+    };
+    let odb = repo.odb()?;
+    let mut seen = HashSet::new();
+    tree_bytes(
+        repo,
+        &odb,
+        repo.find_commit(last_pushed)?.tree_id(),
+        &mut seen,
+    )?;
+
+    let mut bytes = 0;
+    for id in commits {
+        bytes += odb.read_header(*id)?.0 as u64;
+        bytes += tree_bytes(repo, &odb, repo.find_commit(*id)?.tree_id(), &mut seen)?;
+    }
+    Ok(bytes)
+}
+
+/// Return the size of all objects in `tree` not yet in `seen`, and add them to it.
+fn tree_bytes(
+    repo: &git2::Repository,
+    odb: &git2::Odb,
+    tree: git2::Oid,
+    seen: &mut HashSet<git2::Oid>,
+) -> Result<u64> {
+    let mut bytes = 0;
+    let mut stack = vec![tree];
+    while let Some(id) = stack.pop() {
+        if !seen.insert(id) {
+            continue;
+        }
+        let (size, kind) = odb.read_header(id)?;
+        bytes += size as u64;
+        if kind == git2::ObjectType::Tree {
+            stack.extend(
+                repo.find_tree(id)?
+                    .iter()
+                    // Submodule commits aren't part of this repository.
+                    .filter(|entry| entry.kind() != Some(git2::ObjectType::Commit))
+                    .map(|entry| entry.id()),
+            );
+        }
+    }
+    Ok(bytes)
+}
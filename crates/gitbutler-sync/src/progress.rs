@@ -0,0 +1,104 @@
+//! Reporting the progress of pushing the target history by the objects it sends, so progress moves
+//! evenly even though batches differ a lot in size.
+use anyhow::Result;
+
+use crate::events::Event;
+
+/// Turns the batches pushed so far into [`Event::HistoryProgress`].
+pub(crate) struct Progress {
+    /// For each commit, the estimated objects of it and all commits before it, which are pushed
+    /// before it. `None` if they couldn't be counted, in which case progress is measured in batches.
+    objects_until: Option<Vec<u64>>,
+}
+
+impl Progress {
+    /// Estimate the objects each of `commits` of `repo` adds, newest first, once for the whole push.
+    ///
+    /// Failing to do so isn't fatal, progress is then measured in batches.
+    pub(crate) fn new(repo: &git2::Repository, commits: &[git2::Oid]) -> Self {
+        match estimate_objects(repo, commits) {
+            Ok(objects) => Self::from_objects(objects),
+            Err(err) => {
+                tracing::warn!(
+                    ?err,
+                    "failed to count objects, reporting progress by batches"
+                );
+                Progress {
+                    objects_until: None,
+                }
+            }
+        }
+    }
+
+    /// Create an instance from the estimated `objects` of each commit, newest first.
+    pub(crate) fn from_objects(mut objects: Vec<u64>) -> Self {
+        let mut sum = 0;
+        for objects in objects.iter_mut().rev() {
+            sum += *objects;
+            *objects = sum;
+        }
+        Progress {
+            objects_until: Some(objects),
+        }
+    }
+
+    /// Return the progress once `batch` of `batches` was pushed, which is made of the commits
+    /// starting at index `start`.
+    pub(crate) fn pushed(&self, start: usize, batch: usize, batches: usize) -> Event {
+        match &self.objects_until {
+            Some(objects_until) => Event::HistoryProgress {
+                done: objects_until.get(start).copied().unwrap_or_default(),
+                total: objects_until.first().copied().unwrap_or_default(),
+            },
+            None => Event::HistoryProgress {
+                done: batch as u64,
+                total: batches as u64,
+            },
+        }
+    }
+}
+
+/// Return the objects each of `commits` in `repo` adds, estimated as the commit itself along
+/// with the files it changes compared to its first parent.
+fn estimate_objects(repo: &git2::Repository, commits: &[git2::Oid]) -> Result<Vec<u64>> {
+    commits
+        .iter()
+        .map(|id| {
+            let commit = repo.find_commit(*id)?;
+            let parent = match commit.parents().next() {
+                Some(parent) => Some(parent.tree()?),
+                None => None,
+            };
+            let diff = repo.diff_tree_to_tree(parent.as_ref(), Some(&commit.tree()?), None)?;
+            Ok(1 + diff.deltas().len() as u64)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn progress(event: Event) -> (u64, u64) {
+        match event {
+            Event::HistoryProgress { done, total } => (done, total),
+            event => unreachable!("unexpected event {event:?}"),
+        }
+    }
+
+    #[test]
+    fn objects_are_counted_from_the_oldest_commit() {
+        // The newest commit changes a lot of files, the oldest one only a few.
+        let progress_of = Progress::from_objects(vec![10, 1, 1, 2]);
+        assert_eq!(progress(progress_of.pushed(2, 1, 2)), (3, 14));
+        assert_eq!(progress(progress_of.pushed(0, 2, 2)), (14, 14));
+    }
+
+    #[test]
+    fn without_objects_batches_are_counted() {
+        let progress_of = Progress {
+            objects_until: None,
+        };
+        assert_eq!(progress(progress_of.pushed(2, 1, 4)), (1, 4));
+    }
+}
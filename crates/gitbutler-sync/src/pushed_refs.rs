@@ -0,0 +1,44 @@
+//! The refs that were pushed to the GitButler server along with what they pointed to, so refs that
+//! didn't move since are not pushed again.
+use std::{collections::BTreeMap, path::PathBuf};
+
+use anyhow::Result;
+use gitbutler_project::Project;
+
+/// The full names of pushed refs, like `refs/heads/main`, and the objects they pointed to.
+pub(crate) type PushedRefs = BTreeMap<String, git2::Oid>;
+
+/// Return the refs that were pushed for `project`, which is empty if nothing was pushed yet.
+pub(crate) fn load(project: &Project) -> Result<PushedRefs> {
+    let refs: BTreeMap<String, String> = match std::fs::read(pushed_refs_path(project)) {
+        Ok(refs) => serde_json::from_slice(&refs)?,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(PushedRefs::new()),
+        Err(err) => return Err(err.into()),
+    };
+    refs.into_iter()
+        .map(|(name, target)| Ok((name, target.parse()?)))
+        .collect()
+}
+
+pub(crate) fn save(project: &Project, refs: &PushedRefs) -> Result<()> {
+    let refs: BTreeMap<_, _> = refs
+        .iter()
+        .map(|(name, target)| (name, target.to_string()))
+        .collect();
+    Ok(gitbutler_fs::create_dirs_then_write(
+        pushed_refs_path(project),
+        serde_json::to_vec(&refs)?,
+    )?)
+}
+
+/// Forget which refs were pushed for `project`, so all of them are pushed again.
+pub(crate) fn forget(project: &Project) -> Result<()> {
+    match std::fs::remove_file(pushed_refs_path(project)) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+        _ => Ok(()),
+    }
+}
+
+fn pushed_refs_path(project: &Project) -> PathBuf {
+    crate::mirror::state_path(project, "pushed_refs.json")
+}
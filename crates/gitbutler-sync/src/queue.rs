@@ -0,0 +1,123 @@
+//! Running one push of a project at a time, without losing the pushes requested while one runs.
+use std::sync::{Condvar, Mutex};
+
+use anyhow::Result;
+
+/// Serializes the pushes of a project, coalescing the requests made while one is running into a
+/// single push that follows it.
+///
+/// Each push sends whatever the project looks like when it starts, so a push that starts after a
+/// request does everything the request asked for, no matter who requested it.
+#[derive(Debug, Default)]
+pub struct PushQueue {
+    state: Mutex<State>,
+    /// Notified whenever the queue becomes idle.
+    idle: Condvar,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// No push is running.
+    #[default]
+    Idle,
+    /// A push is running, and nothing was requested since it started.
+    Running,
+    /// A push is running, and another one was requested since it started.
+    Pending,
+}
+
+impl PushQueue {
+    /// Run `push`, unless another push is running already, in which case its caller runs one more
+    /// push once the current one ends, and this returns right away.
+    ///
+    /// `push` is called again for as long as more pushes are requested while it runs.
+    /// The result is the one of the last push, while failures of earlier ones are only logged,
+    /// as they are superseded by that.
+    pub fn run(&self, mut push: impl FnMut() -> Result<()>) -> Result<()> {
+        {
+            let mut state = self.state.lock().unwrap();
+            if *state != State::Idle {
+                *state = State::Pending;
+                return Ok(());
+            }
+            *state = State::Running;
+        }
+        let _idle = IdleOnDrop(self);
+        loop {
+            let res = push();
+            if !self.take_pending() {
+                return res;
+            }
+            if let Err(err) = res {
+                tracing::warn!(?err, "push failed, running the push requested meanwhile");
+            }
+        }
+    }
+
+    /// Run `push` and return its result, waiting for the running push to end first, if any.
+    ///
+    /// Unlike with [`PushQueue::run()`], `push` always runs, which is for pushes whose caller waits
+    /// for them. Pushes requested while it runs are run with `push` as well, but only their
+    /// failures are logged.
+    pub fn run_now<T>(&self, mut push: impl FnMut() -> Result<T>) -> Result<T> {
+        let _idle = self.start_when_idle();
+        let res = push();
+        self.run_pending(|| push().map(|_| ()));
+        res
+    }
+
+    /// Run `work` and return its result, waiting for the running push to end first, if any, so
+    /// no push runs alongside it.
+    ///
+    /// This is for work that changes what pushes rely on and must only happen once, like forcing a
+    /// resync. Pushes requested while it runs are run with `push` afterwards, but only their failures
+    /// are logged.
+    pub fn run_exclusive<T>(
+        &self,
+        work: impl FnOnce() -> Result<T>,
+        push: impl FnMut() -> Result<()>,
+    ) -> Result<T> {
+        let _idle = self.start_when_idle();
+        let res = work();
+        self.run_pending(push);
+        res
+    }
+
+    /// Wait until no push runs, and mark the queue as running one until the returned guard is dropped.
+    fn start_when_idle(&self) -> IdleOnDrop<'_> {
+        let mut state = self
+            .idle
+            .wait_while(self.state.lock().unwrap(), |state| *state != State::Idle)
+            .unwrap();
+        *state = State::Running;
+        IdleOnDrop(self)
+    }
+
+    /// Run `push` for as long as pushes are requested while it runs, logging its failures.
+    fn run_pending(&self, mut push: impl FnMut() -> Result<()>) {
+        while self.take_pending() {
+            if let Err(err) = push() {
+                tracing::warn!(?err, "failed to run the push requested meanwhile");
+            }
+        }
+    }
+
+    /// Return `true` if another push was requested while the running one ran, which then keeps running.
+    fn take_pending(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let pending = *state == State::Pending;
+        *state = State::Running;
+        pending
+    }
+}
+
+/// Makes the queue idle once the running push ends, even if it panicked, so later pushes can run.
+struct IdleOnDrop<'a>(&'a PushQueue);
+
+impl Drop for IdleOnDrop<'_> {
+    fn drop(&mut self) {
+        let mut state = self.0.state.lock().unwrap_or_else(|err| err.into_inner());
+        *state = State::Idle;
+        self.0.idle.notify_all();
+    }
+}
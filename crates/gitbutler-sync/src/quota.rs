@@ -0,0 +1,60 @@
+//! The storage quota of the GitButler server, which it reports to the client while pushing.
+use std::path::PathBuf;
+
+use anyhow::Result;
+use gitbutler_project::Project;
+use serde::{Deserialize, Serialize};
+
+/// How much storage the project uses on the server, and how much it may use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageQuota {
+    pub used_bytes: u64,
+    pub total_bytes: u64,
+}
+
+/// Find the last quota reported in `progress`, the progress messages the server sent during a push.
+///
+/// The server reports its quota on a line of its own, like `gitbutler-quota used=1024 total=4096`.
+pub fn parse(progress: &str) -> Option<StorageQuota> {
+    progress
+        .lines()
+        .filter_map(|line| {
+            let fields = line.trim().strip_prefix("gitbutler-quota ")?;
+            let mut used_bytes = None;
+            let mut total_bytes = None;
+            for field in fields.split_whitespace() {
+                match field.split_once('=') {
+                    Some(("used", value)) => used_bytes = Some(value.parse().ok()?),
+                    Some(("total", value)) => total_bytes = Some(value.parse().ok()?),
+                    // Allow the server to report more in future.
+                    _ => {}
+                }
+            }
+            Some(StorageQuota {
+                used_bytes: used_bytes?,
+                total_bytes: total_bytes?,
+            })
+        })
+        .last()
+}
+
+/// Return the quota the server reported last for `project`, if it ever did.
+pub fn last(project: &Project) -> Result<Option<StorageQuota>> {
+    match std::fs::read(quota_path(project)) {
+        Ok(quota) => Ok(Some(serde_json::from_slice(&quota)?)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+pub(crate) fn save(project: &Project, quota: &StorageQuota) -> Result<()> {
+    Ok(gitbutler_fs::create_dirs_then_write(
+        quota_path(project),
+        serde_json::to_vec(quota)?,
+    )?)
+}
+
+fn quota_path(project: &Project) -> PathBuf {
+    crate::mirror::state_path(project, "server_quota.json")
+}
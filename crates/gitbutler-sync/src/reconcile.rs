@@ -0,0 +1,148 @@
+//! Correcting the locally recorded state of code pushes when it doesn't match what the server has.
+//!
+//! The state can drift from the server, e.g. if a push was interrupted before it was recorded, or
+//! if the server lost or changed the ref of the project out-of-band.
+use anyhow::{Context, Result};
+use gitbutler_command_context::CommandContext;
+use gitbutler_project as projects;
+use gitbutler_project::CodePushBackend;
+use gitbutler_user as users;
+use serde::Serialize;
+
+use crate::{
+    anchor,
+    cas::{DirectoryStore, ObjectStore},
+    cloud::{mirror_server_refs, target_refname, update_project},
+    transport::{CodePushTransport, GitTransport},
+};
+
+/// How the recorded state of code pushes related to the server, as found and corrected by [`reconcile()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum Reconciliation {
+    /// The server has exactly what was recorded, so nothing was changed.
+    InSync,
+    /// The server has all recorded commits and more, which are now recorded as pushed.
+    Ahead {
+        #[serde(with = "gitbutler_serde::oid")]
+        server: git2::Oid,
+    },
+    /// The server lacks some of the recorded commits, which will be pushed again.
+    Behind {
+        #[serde(with = "gitbutler_serde::oid")]
+        server: git2::Oid,
+    },
+    /// The server has commits that weren't recorded, and lacks some that were. Only the commits the
+    /// server lacks will be pushed again. The ref of the project is then only overwritten if the
+    /// project [allows force pushes](gitbutler_project::Project::allow_force_push), and otherwise
+    /// the server rejects the next push as non-fast-forward.
+    Diverged {
+        #[serde(with = "gitbutler_serde::oid")]
+        server: git2::Oid,
+    },
+    /// The server has nothing, so the next push starts from scratch.
+    Missing,
+}
+
+/// Return the commit the ref of the project of `ctx` points to on the server, as seen by `user`
+/// through `transport`, or `None` if the server doesn't have it.
+pub(crate) fn server_target(
+    ctx: &CommandContext,
+    user: &users::User,
+    transport: &dyn CodePushTransport,
+) -> Result<Option<git2::Oid>> {
+    let project = ctx.project();
+    let refname = target_refname(project);
+    Ok(match &project.code_push_backend {
+        CodePushBackend::Git => mirror_server_refs(ctx, user, transport, &refname)?
+            .into_iter()
+            .find_map(|(name, target)| (name == refname).then_some(target)),
+        CodePushBackend::Directory { path } => DirectoryStore::new(path)
+            .read_manifest(&refname)?
+            .map(|manifest| manifest.target),
+    })
+}
+
+/// Compare the last pushed commit of the project of `ctx` with the ref of the project on the
+/// server, as seen by `user`, and record what the server actually has.
+///
+/// Subsequent pushes then send exactly what the server lacks.
+pub fn reconcile(
+    ctx: &CommandContext,
+    user: &users::User,
+    projects: &projects::Controller,
+) -> Result<Reconciliation> {
+    reconcile_via(ctx, user, projects, &GitTransport)
+}
+
+/// Like [`reconcile()`], but asking the server with `transport`.
+pub fn reconcile_via(
+    ctx: &CommandContext,
+    user: &users::User,
+    projects: &projects::Controller,
+    transport: &dyn CodePushTransport,
+) -> Result<Reconciliation> {
+    let project = ctx.project();
+    let repo = ctx.repo();
+    let server = server_target(ctx, user, transport)?;
+    let recorded = project
+        .gitbutler_code_push_state
+        .as_ref()
+        .map(|state| state.id);
+
+    let reconciliation = match (recorded, server) {
+        (None, None) => Reconciliation::InSync,
+        (Some(recorded), Some(server)) if recorded == server => Reconciliation::InSync,
+        (Some(_), None) => Reconciliation::Missing,
+        (recorded, Some(server)) => {
+            repo.find_commit(server).with_context(|| {
+                format!("the commit {server} the server has isn't available locally")
+            })?;
+            match recorded {
+                None => Reconciliation::Ahead { server },
+                Some(recorded) if repo.graph_descendant_of(server, recorded)? => {
+                    Reconciliation::Ahead { server }
+                }
+                Some(recorded) if repo.graph_descendant_of(recorded, server)? => {
+                    Reconciliation::Behind { server }
+                }
+                Some(_) => Reconciliation::Diverged { server },
+            }
+        }
+    };
+
+    match reconciliation {
+        Reconciliation::InSync => {}
+        Reconciliation::Missing => {
+            projects
+                .update(&projects::UpdateRequest {
+                    id: project.id,
+                    unset_gitbutler_code_push_state: true,
+                    ..Default::default()
+                })
+                .context("failed to reset last push")?;
+        }
+        Reconciliation::Ahead { server }
+        | Reconciliation::Behind { server }
+        | Reconciliation::Diverged { server } => {
+            update_project(
+                projects,
+                project,
+                server,
+                project.gitbutler_code_push_state.as_ref(),
+            )?;
+        }
+    }
+    if reconciliation != Reconciliation::InSync {
+        // What was pushed last may not be on the server either.
+        anchor::forget(project)?;
+        tracing::info!(
+            project_id = %project.id,
+            ?recorded,
+            ?server,
+            ?reconciliation,
+            "corrected the state of code pushes",
+        );
+    }
+    Ok(reconciliation)
+}
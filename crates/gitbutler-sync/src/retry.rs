@@ -0,0 +1,130 @@
+//! Retrying pushes that failed as the network was down, which often is only briefly the case.
+use std::time::Duration;
+
+use anyhow::Result;
+
+use crate::cloud::RemoteError;
+
+/// How long to wait before the first retry. The wait doubles with each further retry.
+pub(crate) const INITIAL_BACKOFF: Duration = Duration::from_millis(250);
+
+/// Call `push` of `refspec` and return its result, but call it up to `retries` more times while
+/// it fails with [`RemoteError::Network`], using `sleep` to back off exponentially in between.
+///
+/// Other failures are returned right away, as trying again wouldn't change them.
+pub(crate) fn with_network_retries<T>(
+    retries: u32,
+    refspec: &str,
+    sleep: impl Fn(Duration),
+    mut push: impl FnMut() -> Result<T>,
+) -> Result<T> {
+    let mut attempt = 0;
+    loop {
+        match push() {
+            Err(err) if attempt < retries && is_network_failure(&err) => {
+                attempt += 1;
+                let backoff = INITIAL_BACKOFF.saturating_mul(1 << (attempt - 1).min(16));
+                tracing::warn!(
+                    attempt,
+                    retries,
+                    refspec,
+                    ?backoff,
+                    ?err,
+                    "retrying push after network failure",
+                );
+                sleep(backoff);
+            }
+            res => return res,
+        }
+    }
+}
+
+/// Return `true` if `err` is a [`RemoteError::Network`].
+pub(crate) fn is_network_failure(err: &anyhow::Error) -> bool {
+    matches!(
+        err.downcast_ref::<RemoteError>(),
+        Some(RemoteError::Network(_))
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::{Cell, RefCell};
+
+    use super::*;
+
+    fn network_error() -> anyhow::Error {
+        RemoteError::new(
+            git2::Error::new(
+                git2::ErrorCode::GenericError,
+                git2::ErrorClass::Net,
+                "failed to connect",
+            ),
+            "push failed",
+        )
+        .into()
+    }
+
+    #[test]
+    fn network_failures_are_retried_with_backoff() {
+        let sleeps = RefCell::new(Vec::new());
+        let calls = Cell::new(0);
+        let res = with_network_retries(
+            3,
+            "+ref:ref",
+            |backoff| sleeps.borrow_mut().push(backoff),
+            || {
+                calls.set(calls.get() + 1);
+                if calls.get() < 3 {
+                    Err(network_error())
+                } else {
+                    Ok(calls.get())
+                }
+            },
+        );
+        assert_eq!(res.unwrap(), 3);
+        assert_eq!(
+            sleeps.into_inner(),
+            [Duration::from_millis(250), Duration::from_millis(500)]
+        );
+    }
+
+    #[test]
+    fn the_last_network_failure_is_returned() {
+        let sleeps = RefCell::new(Vec::new());
+        let res: Result<()> = with_network_retries(
+            3,
+            "+ref:ref",
+            |backoff| sleeps.borrow_mut().push(backoff),
+            || Err(network_error()),
+        );
+        assert!(matches!(
+            res.unwrap_err().downcast_ref::<RemoteError>(),
+            Some(RemoteError::Network(_))
+        ));
+        assert_eq!(
+            sleeps.into_inner(),
+            [
+                Duration::from_millis(250),
+                Duration::from_millis(500),
+                Duration::from_secs(1)
+            ]
+        );
+    }
+
+    #[test]
+    fn other_failures_are_not_retried() {
+        let calls = Cell::new(0);
+        let res: Result<()> = with_network_retries(
+            3,
+            "+ref:ref",
+            |_backoff| panic!("there is no retry"),
+            || {
+                calls.set(calls.get() + 1);
+                Err(RemoteError::rejected("refs/heads/main", "hook declined").into())
+            },
+        );
+        assert!(res.is_err());
+        assert_eq!(calls.get(), 1);
+    }
+}
@@ -16,7 +16,7 @@ use gitbutler_user::User;
 use gix::bstr::ByteSlice;
 use rand::Rng;
 
-use crate::cloud::{push_to_gitbutler_server, remote, RemoteKind};
+use crate::cloud::{push_to_gitbutler_server, remote_url, RefUpdates, RemoteKind};
 
 /// Pushes all the branches in a stack, starting at the specified top_branch.
 pub fn push_stack_to_review(
@@ -57,8 +57,15 @@ pub fn push_stack_to_review(
 
     let refspec = format_refspec(&review_head);
 
-    let remote = remote(ctx, RemoteKind::Oplog)?;
-    push_to_gitbutler_server(ctx, Some(user), &[&refspec], remote)?;
+    push_to_gitbutler_server(
+        ctx,
+        Some(user),
+        &[&refspec],
+        RefUpdates::Independent,
+        git2_repository,
+        &remote_url(ctx, RemoteKind::Oplog)?.to_string(),
+        &|_event| {},
+    )?;
 
     let Some(head_review) = branch_heads.first() else {
         bail!("No head review id. Congratuations, this is not possible")
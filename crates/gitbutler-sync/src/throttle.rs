@@ -0,0 +1,58 @@
+//! Limiting the rate at which code pushes send data, so they don't saturate the uplink.
+use std::time::{Duration, Instant};
+
+/// Keeps the bytes sent since it was started at or below a rate on average.
+///
+/// Pushes can't be slowed down while they send, so instead the sender waits after each push
+/// until the bytes sent so far are within the rate, which holds back the next one.
+pub(crate) struct Throttle {
+    bytes_per_second: u64,
+    started: Instant,
+    sent: u64,
+}
+
+impl Throttle {
+    /// Start limiting to `bytes_per_second` at `now`, or not at all if it's 0.
+    pub(crate) fn new(bytes_per_second: u64, now: Instant) -> Option<Self> {
+        (bytes_per_second > 0).then_some(Throttle {
+            bytes_per_second,
+            started: now,
+            sent: 0,
+        })
+    }
+
+    /// Record that `bytes` were sent, and return how long to wait after `now` until sending more.
+    pub(crate) fn sent(&mut self, bytes: u64, now: Instant) -> Duration {
+        self.sent = self.sent.saturating_add(bytes);
+        let allowed_after =
+            Duration::from_secs_f64(self.sent as f64 / self.bytes_per_second as f64);
+        allowed_after.saturating_sub(now.saturating_duration_since(self.started))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn waits_until_the_bytes_sent_so_far_are_within_the_rate() {
+        let started = Instant::now();
+        let mut throttle = Throttle::new(100, started).unwrap();
+        assert_eq!(throttle.sent(200, started), Duration::from_secs(2));
+        assert_eq!(
+            throttle.sent(100, started + Duration::from_secs(2)),
+            Duration::from_secs(1),
+            "the rate applies to everything sent since the start"
+        );
+        assert_eq!(
+            throttle.sent(100, started + Duration::from_secs(10)),
+            Duration::ZERO,
+            "sending less than allowed doesn't wait"
+        );
+    }
+
+    #[test]
+    fn zero_is_no_limit() {
+        assert!(Throttle::new(0, Instant::now()).is_none());
+    }
+}
@@ -0,0 +1,165 @@
+//! Exchanging code with the GitButler server, which can be replaced to push and fetch without one.
+use std::{collections::VecDeque, sync::Mutex};
+
+use anyhow::Result;
+use gitbutler_command_context::CommandContext;
+use gitbutler_user as users;
+
+use crate::{
+    cloud::{
+        fetch_from_gitbutler_server, push_to_gitbutler_server, remote_url, Pushed, RefUpdates,
+        RemoteError, RemoteKind,
+    },
+    events::Event,
+};
+
+/// Sends refs and the objects they need to the code remote of a project, and fetches them back.
+pub trait CodePushTransport: Send + Sync {
+    /// Push `ref_specs` of `repo` to the code remote of the project of `ctx` with the credentials
+    /// of `user`, updating the refs according to `updates`, and return what the server did with them.
+    fn push(
+        &self,
+        ctx: &CommandContext,
+        user: &users::User,
+        repo: &git2::Repository,
+        ref_specs: &[&str],
+        updates: RefUpdates,
+        on_event: &dyn Fn(Event),
+    ) -> Result<Pushed>;
+
+    /// Fetch `ref_specs` from the code remote of the project of `ctx` into its repository with the
+    /// credentials of `user`, pruning local refs that don't exist on the server anymore.
+    fn fetch(&self, ctx: &CommandContext, user: &users::User, ref_specs: &[&str]) -> Result<()>;
+}
+
+/// Pushes to and fetches from the GitButler server with `git`.
+pub struct GitTransport;
+
+impl CodePushTransport for GitTransport {
+    fn push(
+        &self,
+        ctx: &CommandContext,
+        user: &users::User,
+        repo: &git2::Repository,
+        ref_specs: &[&str],
+        updates: RefUpdates,
+        on_event: &dyn Fn(Event),
+    ) -> Result<Pushed> {
+        let url = remote_url(ctx, RemoteKind::Code)?.to_string();
+        push_to_gitbutler_server(ctx, Some(user), ref_specs, updates, repo, &url, on_event)
+    }
+
+    fn fetch(&self, ctx: &CommandContext, user: &users::User, ref_specs: &[&str]) -> Result<()> {
+        fetch_from_gitbutler_server(ctx, user, ref_specs)
+    }
+}
+
+/// The ways the [`FakeTransport`] can be told to fail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FakeFailure {
+    /// Fail with [`RemoteError::Network`].
+    Network,
+    /// Fail with [`RemoteError::Auth`].
+    Auth,
+    /// Fail with [`RemoteError::NonFastForward`] for the first pushed ref.
+    NonFastForward,
+}
+
+/// A server that is kept in memory, which records the pushes it receives and accepts all of them,
+/// unless told to fail.
+///
+/// It doesn't serve any refs, so fetches from it only record their ref specs.
+#[derive(Debug, Default)]
+pub struct FakeTransport {
+    received: Mutex<Vec<Vec<String>>>,
+    fetched: Mutex<Vec<Vec<String>>>,
+    /// What the next pushes do, with `None` succeeding, and all pushes succeeding once it's empty.
+    outcomes: Mutex<VecDeque<Option<FakeFailure>>>,
+}
+
+impl FakeTransport {
+    /// Make the next push fail with `failure`, after the outcomes that were queued before it.
+    pub fn fail_next(&self, failure: FakeFailure) {
+        self.fail_after(0, failure);
+    }
+
+    /// Make the push after the next `pushes` fail with `failure`, after the outcomes that were
+    /// queued before it.
+    pub fn fail_after(&self, pushes: usize, failure: FakeFailure) {
+        let mut outcomes = self.outcomes.lock().unwrap();
+        outcomes.extend(std::iter::repeat_n(None, pushes));
+        outcomes.push_back(Some(failure));
+    }
+
+    /// Return the ref specs of each push received so far, in the order they came in.
+    ///
+    /// Failed pushes aren't received, and thus aren't listed.
+    pub fn received(&self) -> Vec<Vec<String>> {
+        self.received.lock().unwrap().clone()
+    }
+
+    /// Return the ref specs of each fetch so far, in the order they came in.
+    pub fn fetched(&self) -> Vec<Vec<String>> {
+        self.fetched.lock().unwrap().clone()
+    }
+}
+
+impl CodePushTransport for FakeTransport {
+    fn push(
+        &self,
+        _ctx: &CommandContext,
+        _user: &users::User,
+        _repo: &git2::Repository,
+        ref_specs: &[&str],
+        _updates: RefUpdates,
+        _on_event: &dyn Fn(Event),
+    ) -> Result<Pushed> {
+        let destination = |ref_spec: &str| {
+            ref_spec
+                .split_once(':')
+                .map(|(_src, dst)| dst.to_owned())
+                .unwrap_or_default()
+        };
+        if let Some(Some(failure)) = self.outcomes.lock().unwrap().pop_front() {
+            let err = match failure {
+                FakeFailure::Network => RemoteError::Network(git2::Error::new(
+                    git2::ErrorCode::GenericError,
+                    git2::ErrorClass::Net,
+                    "failed to connect to the fake server",
+                )),
+                FakeFailure::Auth => RemoteError::Auth(git2::Error::new(
+                    git2::ErrorCode::Auth,
+                    git2::ErrorClass::Http,
+                    "the fake server didn't accept the credentials",
+                )),
+                FakeFailure::NonFastForward => RemoteError::NonFastForward {
+                    refname: ref_specs.first().map(|ref_spec| destination(ref_spec)),
+                },
+            };
+            return Err(err.into());
+        }
+        self.received.lock().unwrap().push(
+            ref_specs
+                .iter()
+                .map(|ref_spec| ref_spec.to_string())
+                .collect(),
+        );
+        Ok(Pushed {
+            anything_pushed: !ref_specs.is_empty(),
+            refs: ref_specs
+                .iter()
+                .map(|ref_spec| destination(ref_spec))
+                .collect(),
+        })
+    }
+
+    fn fetch(&self, _ctx: &CommandContext, _user: &users::User, ref_specs: &[&str]) -> Result<()> {
+        self.fetched.lock().unwrap().push(
+            ref_specs
+                .iter()
+                .map(|ref_spec| ref_spec.to_string())
+                .collect(),
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,82 @@
+use gitbutler_sync::cas::{self, DirectoryStore, ObjectStore};
+use gitbutler_testsupport::{empty_bare_repository, temp_dir, test_repository};
+
+/// Commit a nested file on top of `HEAD` of `repo`.
+fn commit_file(repo: &git2::Repository, path: &str, content: &str) -> git2::Oid {
+    let head = repo.head().unwrap().peel_to_commit().unwrap();
+    let blob = repo.blob(content.as_bytes()).unwrap();
+    let mut subdir = repo.treebuilder(None).unwrap();
+    subdir.insert(path, blob, 0o100644).unwrap();
+    let subdir = subdir.write().unwrap();
+    let mut root = repo.treebuilder(Some(&head.tree().unwrap())).unwrap();
+    root.insert("dir", subdir, 0o040000).unwrap();
+    let tree = repo.find_tree(root.write().unwrap()).unwrap();
+    let signature = git2::Signature::now("test", "test@example.com").unwrap();
+    repo.commit(
+        Some("HEAD"),
+        &signature,
+        &signature,
+        "add file",
+        &tree,
+        &[&head],
+    )
+    .unwrap()
+}
+
+#[test]
+fn refs_resolve_after_round_trip() {
+    let (repo, _repo_tmp) = test_repository();
+    let tip = commit_file(&repo, "file", "content");
+    let store_dir = temp_dir();
+    let store = DirectoryStore::new(store_dir.path());
+
+    assert!(cas::push(&repo, &store, &["+refs/heads/master:refs/heads/master"]).unwrap());
+    assert!(
+        !cas::push(&repo, &store, &[&format!("+{tip}:refs/heads/copy")]).unwrap(),
+        "all objects are already stored"
+    );
+
+    let (other, _other_tmp) = empty_bare_repository();
+    for refname in ["refs/heads/master", "refs/heads/copy"] {
+        assert_eq!(cas::fetch(&other, &store, refname).unwrap(), Some(tip));
+    }
+    let commit = other.find_commit(tip).unwrap();
+    assert_eq!(commit.parent_count(), 1);
+    let entry = commit
+        .tree()
+        .unwrap()
+        .get_path("dir/file".as_ref())
+        .unwrap();
+    assert_eq!(other.find_blob(entry.id()).unwrap().content(), b"content");
+}
+
+#[test]
+fn refs_can_be_deleted() {
+    let (repo, _repo_tmp) = test_repository();
+    let store_dir = temp_dir();
+    let store = DirectoryStore::new(store_dir.path());
+    cas::push(&repo, &store, &["+refs/heads/master:refs/heads/master"]).unwrap();
+
+    cas::push(&repo, &store, &[":refs/heads/master"]).unwrap();
+    assert_eq!(store.read_manifest("refs/heads/master").unwrap(), None);
+    assert_eq!(
+        cas::fetch(&repo, &store, "refs/heads/master").unwrap(),
+        None
+    );
+}
+
+#[test]
+fn corrupted_objects_are_detected() {
+    let (repo, _repo_tmp) = test_repository();
+    let tip = repo.head().unwrap().target().unwrap();
+    let store_dir = temp_dir();
+    let store = DirectoryStore::new(store_dir.path());
+    cas::push(&repo, &store, &["+refs/heads/master:refs/heads/master"]).unwrap();
+    store
+        .write(tip, git2::ObjectType::Commit, b"not the original commit")
+        .unwrap();
+
+    let (other, _other_tmp) = empty_bare_repository();
+    let err = cas::fetch(&other, &store, "refs/heads/master").unwrap_err();
+    assert!(err.to_string().contains("is corrupted"));
+}
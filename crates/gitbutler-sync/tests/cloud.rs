@@ -0,0 +1,2677 @@
+use std::{
+    cell::RefCell,
+    time::{Duration, Instant},
+};
+
+use but_settings::AppSettings;
+use gitbutler_command_context::CommandContext;
+use gitbutler_error::error::Code;
+use gitbutler_project::{CodePushMirror, PushErrorKind, RefRetention, UpdateRequest};
+use gitbutler_reference::{LocalRefname, Refname, RemoteRefname};
+use gitbutler_stack::VirtualBranchesHandle;
+use gitbutler_sync::{
+    budget::{PushBudget, PushOutcome},
+    cloud::{
+        categorized_push_refs, check_push_permission, commits_left_to_push, fetch_remote_branches,
+        fetch_remote_branches_via, force_resync, push_repo, push_repo_via, push_virtual_branch,
+        sync_now, sync_status, PushRefs, RemoteError, ServerBranch, SyncOutcome, SyncStatus,
+    },
+    events::{Event, PushPhase},
+    transport::{FakeFailure, FakeTransport, GitTransport},
+};
+use gitbutler_testsupport::{empty_bare_repository, Suite};
+use tokio_util::sync::CancellationToken;
+
+use crate::{api_project, linear_history, link_to};
+
+mod sync_status {
+    use super::*;
+
+    fn link_with(
+        suite: &Suite,
+        case: &gitbutler_testsupport::Case,
+        url: Option<String>,
+        sync_code: bool,
+    ) {
+        suite
+            .projects
+            .update(&UpdateRequest {
+                id: case.project.id,
+                api: Some(gitbutler_project::ApiProject {
+                    sync_code,
+                    ..api_project(url)
+                }),
+                ..Default::default()
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn is_disabled_unless_code_sync_is_turned_on() {
+        let suite = Suite::default();
+        let user = suite.sign_in();
+        let case = suite.new_case();
+        assert_eq!(
+            sync_status(&case.ctx, Some(&user)).unwrap(),
+            SyncStatus::Disabled,
+            "the project isn't linked"
+        );
+
+        link_with(
+            &suite,
+            &case,
+            Some("https://example.com/code.git".into()),
+            false,
+        );
+        let case = case.refresh(&suite);
+        assert_eq!(
+            sync_status(&case.ctx, Some(&user)).unwrap(),
+            SyncStatus::Disabled
+        );
+    }
+
+    #[test]
+    fn needs_a_code_url() {
+        let suite = Suite::default();
+        let user = suite.sign_in();
+        let case = suite.new_case();
+        link_with(&suite, &case, None, true);
+        let case = case.refresh(&suite);
+        assert_eq!(
+            sync_status(&case.ctx, Some(&user)).unwrap(),
+            SyncStatus::NoCodeUrl
+        );
+    }
+
+    #[test]
+    fn needs_a_user() {
+        let suite = Suite::default();
+        let case = suite.new_case();
+        link_with(
+            &suite,
+            &case,
+            Some("https://example.com/code.git".into()),
+            true,
+        );
+        let case = case.refresh(&suite);
+        assert_eq!(sync_status(&case.ctx, None).unwrap(), SyncStatus::NoUser);
+    }
+
+    #[test]
+    fn is_pending_until_the_target_is_pushed() {
+        let suite = Suite::default();
+        let user = suite.sign_in();
+        let (server, _server_tmp) = empty_bare_repository();
+        let case = suite.new_case();
+        gitbutler_testsupport::virtual_branches::set_test_target(&case.ctx).unwrap();
+        link_with(
+            &suite,
+            &case,
+            Some(server.path().to_str().unwrap().to_owned()),
+            true,
+        );
+        let case = case.refresh(&suite);
+        let commits_behind = commits_left_to_push(&case.ctx).unwrap();
+        assert!(commits_behind > 0);
+        assert_eq!(
+            sync_status(&case.ctx, Some(&user)).unwrap(),
+            SyncStatus::Pending { commits_behind }
+        );
+
+        push_repo(
+            &case.ctx,
+            &user,
+            &suite.projects,
+            &PushBudget::default(),
+            &CancellationToken::new(),
+            &|_event| {},
+        )
+        .unwrap();
+        let case = case.refresh(&suite);
+        assert_eq!(
+            sync_status(&case.ctx, Some(&user)).unwrap(),
+            SyncStatus::UpToDate
+        );
+    }
+}
+
+mod uninitialized_gitbutler_state {
+    use super::*;
+
+    #[test]
+    fn is_skipped_with_event() {
+        let suite = Suite::default();
+        let user = suite.sign_in();
+        let case = suite.new_case();
+
+        let events = RefCell::new(Vec::new());
+        push_repo(
+            &case.ctx,
+            &user,
+            &suite.projects,
+            &PushBudget::default(),
+            &CancellationToken::new(),
+            &|event| events.borrow_mut().push(event),
+        )
+        .unwrap();
+        assert_eq!(
+            events.into_inner(),
+            vec![
+                Event::Phase(PushPhase::ReadingTarget),
+                Event::SkippedGbRepoUninitialized
+            ]
+        );
+    }
+
+    #[test]
+    fn corrupted_state_is_an_error() {
+        let suite = Suite::default();
+        let user = suite.sign_in();
+        let case = suite.new_case();
+        std::fs::write(
+            case.project.gb_dir().join("virtual_branches.toml"),
+            "this isn't [toml",
+        )
+        .unwrap();
+
+        let err = push_repo(
+            &case.ctx,
+            &user,
+            &suite.projects,
+            &PushBudget::default(),
+            &CancellationToken::new(),
+            &|_event| {},
+        )
+        .unwrap_err();
+        assert!(err
+            .to_string()
+            .ends_with("is corrupted and needs to be repaired or removed"));
+    }
+}
+
+#[test]
+fn push_reports_its_phases() {
+    let suite = Suite::default();
+    let user = suite.sign_in();
+    let (server, _server_tmp) = empty_bare_repository();
+    let case = suite.new_case();
+    gitbutler_testsupport::virtual_branches::set_test_target(&case.ctx).unwrap();
+    let case = link_to(&suite, case, &server);
+
+    let events = RefCell::new(Vec::new());
+    push_repo(
+        &case.ctx,
+        &user,
+        &suite.projects,
+        &PushBudget::default(),
+        &CancellationToken::new(),
+        &|event| events.borrow_mut().push(event),
+    )
+    .unwrap();
+    let (phases, others): (Vec<_>, Vec<_>) = events
+        .into_inner()
+        .into_iter()
+        .partition(|event| matches!(event, Event::Phase(_)));
+    let (progress, others): (Vec<_>, Vec<_>) = others.into_iter().partition(|event| {
+        matches!(
+            event,
+            Event::PushStarted { .. }
+                | Event::BatchPushed { .. }
+                | Event::HistoryProgress { .. }
+                | Event::HistoryPushEnded { .. }
+        )
+    });
+    let (transfers, metrics): (Vec<_>, Vec<_>) = others
+        .into_iter()
+        .partition(|event| matches!(event, Event::Transferred { .. } | Event::RefsPushed { .. }));
+    assert_eq!(
+        phases,
+        [
+            PushPhase::ReadingTarget,
+            PushPhase::WalkingHistory,
+            PushPhase::PushingHistory {
+                batch: 1,
+                batches: 1
+            },
+            PushPhase::PushingTarget,
+            PushPhase::PushingRefs,
+            PushPhase::PushingSubmodules,
+            PushPhase::Done,
+        ]
+        .map(Event::Phase)
+    );
+    assert!(
+        matches!(
+            metrics[..],
+            [Event::MemoryUsage { current_bytes, peak_bytes }]
+                if current_bytes > 0 && current_bytes == peak_bytes
+        ),
+        "memory is reported after each batch: {metrics:?}"
+    );
+    assert!(
+        matches!(
+            progress[..],
+            [
+                Event::PushStarted { batches: 1, .. },
+                Event::BatchPushed {
+                    completed: 1,
+                    total: 1
+                },
+                Event::HistoryProgress { done, total },
+                Event::HistoryPushEnded {
+                    completed: 1,
+                    total: 1,
+                    last_pushed: Some(_),
+                },
+            ] if done > 0 && done == total
+        ),
+        "the progress of pushing history is reported: {progress:?}"
+    );
+    assert!(
+        transfers
+            .iter()
+            .any(|event| matches!(event, Event::Transferred { objects, bytes } if *objects > 0 && *bytes > 0)),
+        "what each push sends is reported: {transfers:?}"
+    );
+    let case = case.refresh(&suite);
+    let state = case.project.gitbutler_code_push_state.unwrap();
+    assert!(state.duration_ms.is_some());
+    assert!(
+        state.objects_pushed.is_some_and(|objects| objects > 0),
+        "the objects of all pushes are recorded"
+    );
+    assert!(state.bytes_pushed.is_some_and(|bytes| bytes > 0));
+    assert!(server
+        .find_reference(&format!("refs/{}", case.project.id))
+        .is_ok());
+    assert!(
+        server
+            .find_reference(&format!("refs/push-tmp/{}-batch-1-of-1", case.project.id))
+            .is_err(),
+        "the batch ref is deleted once the target is pushed"
+    );
+}
+
+#[test]
+fn missing_target_commits_are_an_error() {
+    let suite = Suite::default();
+    let user = suite.sign_in();
+    let (server, _server_tmp) = empty_bare_repository();
+    let case = suite.new_case();
+    gitbutler_testsupport::virtual_branches::set_test_target(&case.ctx).unwrap();
+    let vb_state = VirtualBranchesHandle::new(case.project.gb_dir());
+    let mut target = vb_state.get_default_target().unwrap();
+    let missing: git2::Oid = "0123456789abcdef0123456789abcdef01234567".parse().unwrap();
+    target.sha = missing;
+    vb_state.set_default_target(target).unwrap();
+    let case = link_to(&suite, case, &server);
+
+    let err = push_repo(
+        &case.ctx,
+        &user,
+        &suite.projects,
+        &PushBudget::default(),
+        &CancellationToken::new(),
+        &|_event| {},
+    )
+    .unwrap_err();
+    let message = format!("{err:#}");
+    assert!(message.contains(&missing.to_string()), "{message}");
+    assert!(message.contains("refs/remotes/origin/master"), "{message}");
+    assert_eq!(err.downcast_ref::<Code>(), Some(&Code::Validation));
+    assert!(
+        server.references().unwrap().next().is_none(),
+        "nothing was pushed"
+    );
+}
+
+#[test]
+fn files_larger_than_the_limit_are_errors_before_anything_is_pushed() {
+    let suite = Suite::default();
+    let user = suite.sign_in();
+    let (server, _server_tmp) = empty_bare_repository();
+    let case = suite.new_case();
+    {
+        let repo = case.ctx.repo();
+        let signature = git2::Signature::now("test", "test@example.com").unwrap();
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        let mut assets = repo.treebuilder(None).unwrap();
+        assets
+            .insert("video.mp4", repo.blob(&[0; 2048]).unwrap(), 0o100644)
+            .unwrap();
+        assets
+            .insert("logo.png", repo.blob(&[0; 512]).unwrap(), 0o100644)
+            .unwrap();
+        let assets = assets.write().unwrap();
+        let mut tree = repo.treebuilder(Some(&head.tree().unwrap())).unwrap();
+        tree.insert("assets", assets, 0o040000).unwrap();
+        let tree = repo.find_tree(tree.write().unwrap()).unwrap();
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            "add assets",
+            &tree,
+            &[&head],
+        )
+        .unwrap();
+    }
+    gitbutler_testsupport::virtual_branches::set_test_target(&case.ctx).unwrap();
+    let case = link_to(&suite, case, &server);
+    suite
+        .projects
+        .update(&UpdateRequest {
+            id: case.project.id,
+            code_push_max_object_bytes: Some(1024),
+            ..Default::default()
+        })
+        .unwrap();
+    let case = case.refresh(&suite);
+
+    let err = push_repo(
+        &case.ctx,
+        &user,
+        &suite.projects,
+        &PushBudget::default(),
+        &CancellationToken::new(),
+        &|_event| {},
+    )
+    .unwrap_err();
+    let message = format!("{err:#}");
+    assert!(
+        message.contains("'assets/video.mp4' (2048 bytes"),
+        "the file is named along with its size: {message}"
+    );
+    assert!(!message.contains("logo.png"), "{message}");
+    assert_eq!(err.downcast_ref::<Code>(), Some(&Code::Validation));
+    assert!(
+        server.references().unwrap().next().is_none(),
+        "nothing was pushed"
+    );
+    let head = case.ctx.repo().head().unwrap().peel_to_commit().unwrap();
+    assert!(
+        head.tree()
+            .unwrap()
+            .get_path("assets/video.mp4".as_ref())
+            .is_ok(),
+        "the history is left as it is"
+    );
+}
+
+#[test]
+fn history_progress_follows_the_objects_of_the_batches() {
+    let suite = Suite::default();
+    let user = suite.sign_in();
+    let (server, _server_tmp) = empty_bare_repository();
+    let case = suite.new_case();
+    {
+        let repo = case.ctx.repo();
+        let signature = git2::Signature::now("test", "test@example.com").unwrap();
+        // Only the newest commits change files, so the batches differ a lot in size.
+        for n in 0..6 {
+            let head = repo.head().unwrap().peel_to_commit().unwrap();
+            let mut tree = repo.treebuilder(Some(&head.tree().unwrap())).unwrap();
+            for file in 0..n * 5 {
+                let blob = repo.blob(format!("{n} {file}").as_bytes()).unwrap();
+                tree.insert(format!("file-{file}"), blob, 0o100644).unwrap();
+            }
+            let tree = repo.find_tree(tree.write().unwrap()).unwrap();
+            repo.commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                &format!("commit {n}"),
+                &tree,
+                &[&head],
+            )
+            .unwrap();
+        }
+    }
+    gitbutler_testsupport::virtual_branches::set_test_target(&case.ctx).unwrap();
+    let case = link_to(&suite, case, &server);
+    suite
+        .projects
+        .update(&UpdateRequest {
+            id: case.project.id,
+            code_push_batch_size: Some(2),
+            ..Default::default()
+        })
+        .unwrap();
+    let case = case.refresh(&suite);
+
+    let events = RefCell::new(Vec::new());
+    push_repo(
+        &case.ctx,
+        &user,
+        &suite.projects,
+        &PushBudget::default(),
+        &CancellationToken::new(),
+        &|event| events.borrow_mut().push(event),
+    )
+    .unwrap();
+    let events = events.into_inner();
+    let fractions: Vec<_> = events
+        .iter()
+        .filter_map(|event| match event {
+            Event::HistoryProgress { done, total } => Some(*done as f64 / *total as f64),
+            _ => None,
+        })
+        .collect();
+    let batches = events
+        .iter()
+        .filter(|event| matches!(event, Event::BatchPushed { .. }))
+        .count();
+    assert!(batches > 2, "the history is pushed in several batches");
+    assert_eq!(fractions.len(), batches, "progress follows each batch");
+    assert!(
+        fractions.windows(2).all(|pair| pair[0] < pair[1]),
+        "progress only moves forward: {fractions:?}"
+    );
+    assert_eq!(fractions.last(), Some(&1.0));
+    assert!(
+        fractions[0] < 1.0 / batches as f64,
+        "the oldest batch has the fewest objects: {fractions:?}"
+    );
+}
+
+#[test]
+fn batch_refs_are_deleted_after_the_target_is_pushed() {
+    let suite = Suite::default();
+    let user = suite.sign_in();
+    let (server, _server_tmp) = empty_bare_repository();
+    let case = suite.new_case();
+    linear_history(case.ctx.repo(), 5);
+    gitbutler_testsupport::virtual_branches::set_test_target(&case.ctx).unwrap();
+    let case = link_to(&suite, case, &server);
+    suite
+        .projects
+        .update(&UpdateRequest {
+            id: case.project.id,
+            code_push_batch_size: Some(2),
+            ..Default::default()
+        })
+        .unwrap();
+    let case = case.refresh(&suite);
+
+    // The first push pauses after a batch, so its batch ref is deleted by the second.
+    let budget = PushBudget {
+        max_batches: Some(1),
+        ..Default::default()
+    };
+    let outcome = push_repo(
+        &case.ctx,
+        &user,
+        &suite.projects,
+        &budget,
+        &CancellationToken::new(),
+        &|_event| {},
+    )
+    .unwrap();
+    assert_eq!(outcome, PushOutcome::Paused);
+    assert_eq!(
+        server.references_glob("refs/push-tmp/*").unwrap().count(),
+        1
+    );
+
+    let case = case.refresh(&suite);
+    push_repo(
+        &case.ctx,
+        &user,
+        &suite.projects,
+        &PushBudget::default(),
+        &CancellationToken::new(),
+        &|_event| {},
+    )
+    .unwrap();
+    assert!(server
+        .find_reference(&format!("refs/{}", case.project.id))
+        .is_ok());
+    assert_eq!(
+        server.references_glob("refs/push-tmp/*").unwrap().count(),
+        0,
+        "no batch ref remains"
+    );
+    assert_eq!(
+        case.ctx
+            .repo()
+            .references_glob("refs/gitbutler-server/*")
+            .unwrap()
+            .count(),
+        0,
+        "nothing of the server is left locally"
+    );
+}
+
+#[test]
+fn batch_refs_of_other_projects_are_left_alone() {
+    let suite = Suite::default();
+    let user = suite.sign_in();
+    let (server, _server_tmp) = empty_bare_repository();
+    let other = {
+        let signature = git2::Signature::now("test", "test@example.com").unwrap();
+        let tree = server.treebuilder(None).unwrap().write().unwrap();
+        let tree = server.find_tree(tree).unwrap();
+        let commit = server
+            .commit(None, &signature, &signature, "other", &tree, &[])
+            .unwrap();
+        server
+            .reference(
+                &format!("refs/push-tmp/{}-1", uuid::Uuid::new_v4()),
+                commit,
+                false,
+                "",
+            )
+            .unwrap();
+        commit
+    };
+    let case = suite.new_case();
+    linear_history(case.ctx.repo(), 5);
+    gitbutler_testsupport::virtual_branches::set_test_target(&case.ctx).unwrap();
+    let case = link_to(&suite, case, &server);
+    suite
+        .projects
+        .update(&UpdateRequest {
+            id: case.project.id,
+            code_push_batch_size: Some(2),
+            ..Default::default()
+        })
+        .unwrap();
+    let case = case.refresh(&suite);
+
+    push_repo(
+        &case.ctx,
+        &user,
+        &suite.projects,
+        &PushBudget::default(),
+        &CancellationToken::new(),
+        &|_event| {},
+    )
+    .unwrap();
+    let remaining: Vec<_> = server
+        .references_glob("refs/push-tmp/*")
+        .unwrap()
+        .map(|reference| reference.unwrap().target())
+        .collect();
+    assert_eq!(
+        remaining,
+        [Some(other)],
+        "only the batch refs of the project are deleted"
+    );
+    assert!(
+        case.ctx.repo().find_commit(other).is_err(),
+        "the history of other projects isn't fetched"
+    );
+}
+
+#[test]
+fn ref_namespaces_are_configured_per_project() {
+    let suite = Suite::default();
+    let user = suite.sign_in();
+    let (server, _server_tmp) = empty_bare_repository();
+    let case = suite.new_case();
+    linear_history(case.ctx.repo(), 3);
+    gitbutler_testsupport::virtual_branches::set_test_target(&case.ctx).unwrap();
+    let case = link_to(&suite, case, &server);
+    suite
+        .projects
+        .update(&UpdateRequest {
+            id: case.project.id,
+            code_push_batch_size: Some(2),
+            code_push_batch_ref_prefix: Some("refs/isolated/tmp".into()),
+            code_push_target_ref_prefix: Some("refs/isolated".into()),
+            ..Default::default()
+        })
+        .unwrap();
+    let case = case.refresh(&suite);
+
+    let budget = PushBudget {
+        max_batches: Some(1),
+        ..Default::default()
+    };
+    push_repo(
+        &case.ctx,
+        &user,
+        &suite.projects,
+        &budget,
+        &CancellationToken::new(),
+        &|_event| {},
+    )
+    .unwrap();
+    assert_eq!(
+        server
+            .references_glob("refs/isolated/tmp/*")
+            .unwrap()
+            .count(),
+        1
+    );
+    assert_eq!(
+        server.references_glob("refs/push-tmp/*").unwrap().count(),
+        0
+    );
+
+    let case = case.refresh(&suite);
+    push_repo(
+        &case.ctx,
+        &user,
+        &suite.projects,
+        &PushBudget::default(),
+        &CancellationToken::new(),
+        &|_event| {},
+    )
+    .unwrap();
+    assert!(server
+        .find_reference(&format!("refs/isolated/{}", case.project.id))
+        .is_ok());
+    assert!(server
+        .find_reference(&format!("refs/{}", case.project.id))
+        .is_err());
+    assert_eq!(
+        server
+            .references_glob("refs/isolated/tmp/*")
+            .unwrap()
+            .count(),
+        0,
+        "batch refs are deleted from the configured namespace"
+    );
+}
+
+#[test]
+fn refs_older_than_the_retention_period_are_pruned() {
+    let suite = Suite::default();
+    let user = suite.sign_in();
+    let (server, _server_tmp) = empty_bare_repository();
+    let case = suite.new_case();
+    gitbutler_testsupport::virtual_branches::set_test_target(&case.ctx).unwrap();
+    {
+        let repo = case.ctx.repo();
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        let a_year_ago = git2::Signature::new(
+            "test",
+            "test@example.com",
+            &git2::Time::new(head.time().seconds() - 365 * 24 * 60 * 60, 0),
+        )
+        .unwrap();
+        repo.commit(
+            Some("refs/heads/stale"),
+            &a_year_ago,
+            &a_year_ago,
+            "old work",
+            &head.tree().unwrap(),
+            &[&head],
+        )
+        .unwrap();
+    }
+    let case = link_to(&suite, case, &server);
+
+    push_repo(
+        &case.ctx,
+        &user,
+        &suite.projects,
+        &PushBudget::default(),
+        &CancellationToken::new(),
+        &|_event| {},
+    )
+    .unwrap();
+    assert!(server.find_reference("refs/heads/stale").is_ok());
+
+    suite
+        .projects
+        .update(&UpdateRequest {
+            id: case.project.id,
+            ref_retention: Some(RefRetention {
+                max_age_days: Some(30),
+                prune: true,
+            }),
+            ..Default::default()
+        })
+        .unwrap();
+    let case = case.refresh(&suite);
+    push_repo(
+        &case.ctx,
+        &user,
+        &suite.projects,
+        &PushBudget::default(),
+        &CancellationToken::new(),
+        &|_event| {},
+    )
+    .unwrap();
+    assert!(server.find_reference("refs/heads/stale").is_err());
+    assert!(server.find_reference("refs/heads/master").is_ok());
+}
+
+#[test]
+fn refs_are_pushed_in_chunks_and_resume_after_the_last_pushed_one() {
+    let suite = Suite::default();
+    let user = suite.sign_in();
+    let (server, _server_tmp) = empty_bare_repository();
+    let case = suite.new_case();
+    gitbutler_testsupport::virtual_branches::set_test_target(&case.ctx).unwrap();
+    let case = link_to(&suite, case, &server);
+    suite
+        .projects
+        .update(&UpdateRequest {
+            id: case.project.id,
+            code_push_batch_size: Some(2),
+            ..Default::default()
+        })
+        .unwrap();
+    let case = case.refresh(&suite);
+    let transport = FakeTransport::default();
+    let push = |case: &gitbutler_testsupport::Case| {
+        push_repo_via(
+            &case.ctx,
+            &user,
+            &suite.projects,
+            &PushBudget::default(),
+            &CancellationToken::new(),
+            &transport,
+            &|_event| {},
+        )
+    };
+    push(&case).unwrap();
+
+    {
+        let repo = case.ctx.repo();
+        let head = repo.head().unwrap().target().unwrap();
+        for n in 1..=5 {
+            repo.reference(&format!("refs/heads/ci-{n}"), head, false, "")
+                .unwrap();
+        }
+    }
+    let force = if *case.project.allow_force_push {
+        "+"
+    } else {
+        ""
+    };
+    let ref_spec = |n: usize| format!("{force}refs/heads/ci-{n}:refs/heads/ci-{n}");
+    let pushed_since = |since: usize| -> Vec<Vec<String>> {
+        transport.received()[since..]
+            .iter()
+            .filter(|ref_specs| ref_specs.iter().any(|ref_spec| ref_spec.contains("ci-")))
+            .cloned()
+            .collect()
+    };
+
+    // The target is pushed again first, then the second chunk of refs fails.
+    transport.fail_after(2, FakeFailure::Network);
+    let received = transport.received().len();
+    push(&case.refresh(&suite)).unwrap_err();
+    assert_eq!(pushed_since(received), [vec![ref_spec(1), ref_spec(2)]]);
+
+    let received = transport.received().len();
+    push(&case.refresh(&suite)).unwrap();
+    assert_eq!(
+        pushed_since(received),
+        [vec![ref_spec(3), ref_spec(4)], vec![ref_spec(5)]],
+        "the refs of the chunk that was pushed aren't pushed again"
+    );
+}
+
+#[test]
+fn a_push_ending_between_target_and_refs_is_finished_by_the_next_one() {
+    let suite = Suite::default();
+    let user = suite.sign_in();
+    let (server, _server_tmp) = empty_bare_repository();
+    let case = suite.new_case();
+    gitbutler_testsupport::virtual_branches::set_test_target(&case.ctx).unwrap();
+    let case = link_to(&suite, case, &server);
+    suite
+        .projects
+        .update(&UpdateRequest {
+            id: case.project.id,
+            code_push_min_interval_seconds: Some(60 * 60),
+            ..Default::default()
+        })
+        .unwrap();
+    let case = case.refresh(&suite);
+    let transport = FakeTransport::default();
+    let push = |case: &gitbutler_testsupport::Case| {
+        push_repo_via(
+            &case.ctx,
+            &user,
+            &suite.projects,
+            &PushBudget::default(),
+            &CancellationToken::new(),
+            &transport,
+            &|_event| {},
+        )
+    };
+    let target = VirtualBranchesHandle::new(case.project.gb_dir())
+        .get_default_target()
+        .unwrap()
+        .sha;
+    let state = || {
+        suite
+            .projects
+            .get(case.project.id)
+            .unwrap()
+            .gitbutler_code_push_state
+            .unwrap()
+    };
+
+    // After the permission check and the target, the refs fail as if the app quit in between.
+    transport.fail_after(2, FakeFailure::Network);
+    push(&case).unwrap_err();
+    let state_after_target = state();
+    assert_eq!(state_after_target.id, target);
+    assert!(
+        state_after_target.refs_pending,
+        "the target is recorded as pushed, but its refs aren't"
+    );
+
+    let received = transport.received().len();
+    push(&case.refresh(&suite)).unwrap();
+    let pushed = &transport.received()[received..];
+    assert!(!pushed.is_empty(), "the push isn't skipped as too recent");
+    assert!(
+        pushed
+            .iter()
+            .flatten()
+            .all(|ref_spec| !ref_spec.contains("refs/push-tmp/")),
+        "the target isn't pushed again: {pushed:?}"
+    );
+    assert!(
+        pushed
+            .iter()
+            .flatten()
+            .any(|ref_spec| ref_spec.ends_with(":refs/heads/master")),
+        "the refs are pushed: {pushed:?}"
+    );
+    assert!(!state().refs_pending, "the push is complete");
+}
+
+#[test]
+fn refs_are_only_pushed_again_once_they_changed() {
+    let suite = Suite::default();
+    let user = suite.sign_in();
+    let (server, _server_tmp) = empty_bare_repository();
+    let case = suite.new_case();
+    {
+        let repo = case.ctx.repo();
+        let head = repo.head().unwrap().target().unwrap();
+        repo.reference("refs/heads/feature", head, false, "")
+            .unwrap();
+    }
+    gitbutler_testsupport::virtual_branches::set_test_target(&case.ctx).unwrap();
+    let case = link_to(&suite, case, &server);
+
+    let push = |case: &gitbutler_testsupport::Case| {
+        let events = RefCell::new(Vec::new());
+        push_repo(
+            &case.ctx,
+            &user,
+            &suite.projects,
+            &PushBudget::default(),
+            &CancellationToken::new(),
+            &|event| events.borrow_mut().push(event),
+        )
+        .unwrap();
+        // Everything after the refs phase is pushing refs and submodules, which there are none of.
+        events
+            .into_inner()
+            .into_iter()
+            .skip_while(|event| *event != Event::Phase(PushPhase::PushingRefs))
+            .filter(|event| matches!(event, Event::Transferred { .. }))
+            .count()
+    };
+    assert_eq!(push(&case), 1, "all refs are pushed at first");
+    assert!(server.find_reference("refs/heads/feature").is_ok());
+
+    let case = case.refresh(&suite);
+    assert_eq!(push(&case), 0, "nothing changed, so nothing is pushed");
+
+    case.ctx
+        .repo()
+        .find_reference("refs/heads/feature")
+        .unwrap()
+        .delete()
+        .unwrap();
+    let case = case.refresh(&suite);
+    assert_eq!(push(&case), 1, "the deleted ref is pushed as deletion");
+    assert!(server.find_reference("refs/heads/feature").is_err());
+    assert!(server.find_reference("refs/heads/master").is_ok());
+
+    let case = case.refresh(&suite);
+    assert_eq!(push(&case), 0);
+}
+
+#[test]
+fn deleted_remote_branches_are_pruned_on_the_server() {
+    let suite = Suite::default();
+    let user = suite.sign_in();
+    let (server, _server_tmp) = empty_bare_repository();
+    let case = suite.new_case();
+    let head = {
+        let repo = case.ctx.repo();
+        let head = repo.head().unwrap().target().unwrap();
+        repo.reference("refs/remotes/origin/feature", head, false, "")
+            .unwrap();
+        head
+    };
+    gitbutler_testsupport::virtual_branches::set_test_target(&case.ctx).unwrap();
+    let case = link_to(&suite, case, &server);
+
+    let push = |case: &gitbutler_testsupport::Case| {
+        push_repo(
+            &case.ctx,
+            &user,
+            &suite.projects,
+            &PushBudget::default(),
+            &CancellationToken::new(),
+            &|_| {},
+        )
+        .unwrap();
+    };
+    push(&case);
+    assert!(server.find_reference("refs/remotes/origin/feature").is_ok());
+    // Refs the server got from elsewhere aren't managed by the push.
+    server
+        .reference("refs/heads/from-elsewhere", head, false, "")
+        .unwrap();
+
+    case.ctx
+        .repo()
+        .find_reference("refs/remotes/origin/feature")
+        .unwrap()
+        .delete()
+        .unwrap();
+    let case = case.refresh(&suite);
+    push(&case);
+    assert!(
+        server
+            .find_reference("refs/remotes/origin/feature")
+            .is_err(),
+        "deleted remote branches are deleted on the server as well"
+    );
+    assert!(server.find_reference("refs/heads/from-elsewhere").is_ok());
+    assert!(server.find_reference("refs/heads/master").is_ok());
+}
+
+#[test]
+fn pushed_refs_are_reported_unless_the_server_had_them_already() {
+    let suite = Suite::default();
+    let user = suite.sign_in();
+    let (server, _server_tmp) = empty_bare_repository();
+    let case = suite.new_case();
+    let head = {
+        let repo = case.ctx.repo();
+        let head = repo.head().unwrap().target().unwrap();
+        repo.reference("refs/remotes/origin/feature", head, false, "")
+            .unwrap();
+        head
+    };
+    gitbutler_testsupport::virtual_branches::set_test_target(&case.ctx).unwrap();
+    let case = link_to(&suite, case, &server);
+
+    let push = |case: &gitbutler_testsupport::Case| {
+        let events = RefCell::new(Vec::new());
+        push_repo(
+            &case.ctx,
+            &user,
+            &suite.projects,
+            &PushBudget::default(),
+            &CancellationToken::new(),
+            &|event| events.borrow_mut().push(event),
+        )
+        .unwrap();
+        events
+            .into_inner()
+            .into_iter()
+            .filter_map(|event| match event {
+                Event::RefsPushed { updated, deleted } => Some((updated, deleted)),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+    let pushed = push(&case);
+    assert!(
+        matches!(&pushed[..], [(updated, deleted)]
+            if updated.contains(&"refs/remotes/origin/feature".parse().unwrap()) && deleted.is_empty()),
+        "the first push reports all refs: {pushed:?}"
+    );
+
+    {
+        let repo = case.ctx.repo();
+        repo.reference("refs/heads/feature", head, false, "")
+            .unwrap();
+        repo.reference("refs/tags/v1", head, false, "").unwrap();
+    }
+    // Without knowing what was pushed, all refs are sent again, but the server has most of them.
+    std::fs::remove_file(case.project.gb_dir().join("pushed_refs.json")).unwrap();
+    let case = case.refresh(&suite);
+    let refname = |name: &str| name.parse::<Refname>().unwrap();
+    assert_eq!(
+        push(&case),
+        [(
+            vec![refname("refs/heads/feature"), refname("refs/tags/v1")],
+            vec![]
+        )],
+        "only refs that changed on the server are reported"
+    );
+
+    case.ctx
+        .repo()
+        .find_reference("refs/tags/v1")
+        .unwrap()
+        .delete()
+        .unwrap();
+    let case = case.refresh(&suite);
+    assert_eq!(
+        push(&case),
+        [(vec![], vec![refname("refs/tags/v1")])],
+        "deleted refs are reported separately"
+    );
+    assert!(server.find_reference("refs/tags/v1").is_err());
+    assert!(push(&case.refresh(&suite)).is_empty(), "nothing changed");
+}
+
+#[test]
+fn rewritten_refs_are_rejected_unless_force_pushes_are_allowed() {
+    let suite = Suite::default();
+    let user = suite.sign_in();
+    let (server, _server_tmp) = empty_bare_repository();
+    let case = suite.new_case();
+    let commit = |repo: &git2::Repository, message: &str| {
+        let signature = git2::Signature::now("test", "test@example.com").unwrap();
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        let id = repo
+            .commit(
+                None,
+                &signature,
+                &signature,
+                message,
+                &head.tree().unwrap(),
+                &[&head],
+            )
+            .unwrap();
+        repo.reference("refs/heads/feature", id, true, "").unwrap();
+        id
+    };
+    let pushed = commit(case.ctx.repo(), "pushed");
+    gitbutler_testsupport::virtual_branches::set_test_target(&case.ctx).unwrap();
+    let case = link_to(&suite, case, &server);
+    suite
+        .projects
+        .update(&UpdateRequest {
+            id: case.project.id,
+            allow_force_push: Some(false),
+            ..Default::default()
+        })
+        .unwrap();
+    let case = case.refresh(&suite);
+    let push = |case: &gitbutler_testsupport::Case| {
+        push_repo(
+            &case.ctx,
+            &user,
+            &suite.projects,
+            &PushBudget::default(),
+            &CancellationToken::new(),
+            &|_event| {},
+        )
+    };
+    push(&case).unwrap();
+    assert_eq!(
+        server.refname_to_id("refs/heads/feature").unwrap(),
+        pushed,
+        "new refs are pushed"
+    );
+
+    // A sibling of the pushed commit rewrites the history of the branch.
+    let rewritten = commit(case.ctx.repo(), "rewritten");
+    let case = case.refresh(&suite);
+    let err = push(&case).unwrap_err();
+    assert!(
+        matches!(
+            err.downcast_ref::<RemoteError>(),
+            Some(RemoteError::NonFastForward { .. })
+        ),
+        "{err:?}"
+    );
+    assert_eq!(server.refname_to_id("refs/heads/feature").unwrap(), pushed);
+
+    suite
+        .projects
+        .update(&UpdateRequest {
+            id: case.project.id,
+            allow_force_push: Some(true),
+            ..Default::default()
+        })
+        .unwrap();
+    let case = case.refresh(&suite);
+    push(&case).unwrap();
+    assert_eq!(
+        server.refname_to_id("refs/heads/feature").unwrap(),
+        rewritten
+    );
+}
+
+#[test]
+fn forced_resyncs_push_everything_again() {
+    let suite = Suite::default();
+    let user = suite.sign_in();
+    let (server, _server_tmp) = empty_bare_repository();
+    let case = suite.new_case();
+    gitbutler_testsupport::virtual_branches::set_test_target(&case.ctx).unwrap();
+    let case = link_to(&suite, case, &server);
+    push_repo(
+        &case.ctx,
+        &user,
+        &suite.projects,
+        &PushBudget::default(),
+        &CancellationToken::new(),
+        &|_event| {},
+    )
+    .unwrap();
+
+    // The project moves to a server that doesn't have anything, which the state doesn't know.
+    let (new_server, _new_server_tmp) = empty_bare_repository();
+    let case = link_to(&suite, case.refresh(&suite), &new_server);
+    push_repo(
+        &case.ctx,
+        &user,
+        &suite.projects,
+        &PushBudget::default(),
+        &CancellationToken::new(),
+        &|_event| {},
+    )
+    .unwrap();
+    assert!(
+        new_server.find_reference("refs/heads/master").is_err(),
+        "refs that didn't change aren't pushed again"
+    );
+
+    let case = case.refresh(&suite);
+    let outcome = force_resync(
+        &case.ctx,
+        &user,
+        &suite.projects,
+        &GitTransport,
+        &|_event| {},
+    )
+    .unwrap();
+    assert_eq!(outcome, PushOutcome::Complete);
+    let master = case.ctx.repo().refname_to_id("refs/heads/master").unwrap();
+    assert_eq!(
+        new_server
+            .find_reference("refs/heads/master")
+            .unwrap()
+            .target(),
+        Some(master)
+    );
+    assert!(new_server
+        .find_reference(&format!("refs/{}", case.project.id))
+        .is_ok());
+    let case = case.refresh(&suite);
+    assert_eq!(
+        case.project.gitbutler_code_push_state.map(|state| state.id),
+        Some(master),
+        "the state is overwritten"
+    );
+}
+
+#[test]
+fn tags_are_pushed() {
+    let suite = Suite::default();
+    let user = suite.sign_in();
+    let (server, _server_tmp) = empty_bare_repository();
+    let case = suite.new_case();
+    let annotated = {
+        let repo = case.ctx.repo();
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        repo.tag_lightweight("lightweight", head.as_object(), false)
+            .unwrap();
+        let signature = git2::Signature::now("test", "test@example.com").unwrap();
+        repo.tag("v1.0", head.as_object(), &signature, "release", false)
+            .unwrap()
+    };
+    gitbutler_testsupport::virtual_branches::set_test_target(&case.ctx).unwrap();
+    let case = link_to(&suite, case, &server);
+
+    push_repo(
+        &case.ctx,
+        &user,
+        &suite.projects,
+        &PushBudget::default(),
+        &CancellationToken::new(),
+        &|_event| {},
+    )
+    .unwrap();
+    assert!(server.find_reference("refs/tags/lightweight").is_ok());
+    assert_eq!(
+        server.find_reference("refs/tags/v1.0").unwrap().target(),
+        Some(annotated),
+        "the tag object itself is pushed"
+    );
+    assert!(server.find_tag(annotated).is_ok());
+}
+
+#[test]
+fn notes_are_pushed() {
+    let suite = Suite::default();
+    let user = suite.sign_in();
+    let (server, _server_tmp) = empty_bare_repository();
+    let case = suite.new_case();
+    let head = {
+        let repo = case.ctx.repo();
+        let head = repo.head().unwrap().target().unwrap();
+        let signature = git2::Signature::now("test", "test@example.com").unwrap();
+        repo.note(&signature, &signature, None, head, "reviewed", false)
+            .unwrap();
+        head
+    };
+    gitbutler_testsupport::virtual_branches::set_test_target(&case.ctx).unwrap();
+    let case = link_to(&suite, case, &server);
+
+    push_repo(
+        &case.ctx,
+        &user,
+        &suite.projects,
+        &PushBudget::default(),
+        &CancellationToken::new(),
+        &|_event| {},
+    )
+    .unwrap();
+    assert!(server.find_reference("refs/notes/commits").is_ok());
+    assert_eq!(
+        server.find_note(None, head).unwrap().message(),
+        Some("reviewed")
+    );
+}
+
+#[test]
+fn excluded_virtual_branches_are_not_pushed() {
+    let suite = Suite::default();
+    let user = suite.sign_in();
+    let (server, _server_tmp) = empty_bare_repository();
+    let case = suite.new_case();
+    {
+        let repo = case.ctx.repo();
+        let head = repo.head().unwrap().target().unwrap();
+        for name in ["refs/gitbutler/feature", "refs/gitbutler/scratch-1"] {
+            repo.reference(name, head, false, "").unwrap();
+        }
+    }
+    gitbutler_testsupport::virtual_branches::set_test_target(&case.ctx).unwrap();
+    let case = link_to(&suite, case, &server);
+    suite
+        .projects
+        .update(&UpdateRequest {
+            id: case.project.id,
+            code_push_excluded_refs: Some(vec!["refs/gitbutler/scratch-*".into()]),
+            ..Default::default()
+        })
+        .unwrap();
+    let case = case.refresh(&suite);
+
+    push_repo(
+        &case.ctx,
+        &user,
+        &suite.projects,
+        &PushBudget::default(),
+        &CancellationToken::new(),
+        &|_event| {},
+    )
+    .unwrap();
+    assert!(server.find_reference("refs/gitbutler/feature").is_ok());
+    assert!(
+        server.find_reference("refs/gitbutler/scratch-1").is_err(),
+        "excluded refs aren't pushed"
+    );
+    assert!(
+        server.find_reference("refs/heads/master").is_ok(),
+        "refs not matching any pattern are unaffected"
+    );
+}
+
+#[test]
+fn bare_repositories_are_pushed() {
+    let suite = Suite::default();
+    let user = suite.sign_in();
+    let (server, _server_tmp) = empty_bare_repository();
+    let case = suite.new_case();
+    gitbutler_testsupport::virtual_branches::set_test_target(&case.ctx).unwrap();
+
+    let mirror_tmp = gitbutler_testsupport::temp_dir();
+    let mirror = git2::build::RepoBuilder::new()
+        .bare(true)
+        .clone(case.project.path.to_str().unwrap(), mirror_tmp.path())
+        .unwrap();
+    let mut project = case.project.clone();
+    project.path = mirror.path().to_owned();
+    std::fs::create_dir_all(project.gb_dir()).unwrap();
+    std::fs::copy(
+        case.project.gb_dir().join("virtual_branches.toml"),
+        project.gb_dir().join("virtual_branches.toml"),
+    )
+    .unwrap();
+    suite
+        .projects
+        .update(&UpdateRequest {
+            id: case.project.id,
+            path: Some(project.path),
+            ..Default::default()
+        })
+        .unwrap();
+    let case = link_to(&suite, case, &server);
+    assert!(case.ctx.repo().is_bare());
+
+    let events = RefCell::new(Vec::new());
+    push_repo(
+        &case.ctx,
+        &user,
+        &suite.projects,
+        &PushBudget::default(),
+        &CancellationToken::new(),
+        &|event| events.borrow_mut().push(event),
+    )
+    .unwrap();
+    assert!(!events
+        .into_inner()
+        .contains(&Event::Phase(PushPhase::PushingSubmodules)));
+    assert!(server
+        .find_reference(&format!("refs/{}", case.project.id))
+        .is_ok());
+    assert!(server.find_reference("refs/heads/master").is_ok());
+}
+
+#[test]
+fn batch_size_is_configured_per_project() {
+    for (configured, expected_batches) in [(Some(2), 2), (Some(0), 4), (None, 1)] {
+        let suite = Suite::default();
+        let user = suite.sign_in();
+        let (server, _server_tmp) = empty_bare_repository();
+        let case = suite.new_case();
+        linear_history(case.ctx.repo(), 3);
+        gitbutler_testsupport::virtual_branches::set_test_target(&case.ctx).unwrap();
+        let case = link_to(&suite, case, &server);
+        suite
+            .projects
+            .update(&UpdateRequest {
+                id: case.project.id,
+                code_push_batch_size: configured,
+                ..Default::default()
+            })
+            .unwrap();
+        let case = case.refresh(&suite);
+
+        let events = RefCell::new(Vec::new());
+        push_repo(
+            &case.ctx,
+            &user,
+            &suite.projects,
+            &PushBudget::default(),
+            &CancellationToken::new(),
+            &|event| events.borrow_mut().push(event),
+        )
+        .unwrap();
+        let batches = events
+            .into_inner()
+            .into_iter()
+            .filter(|event| matches!(event, Event::Phase(PushPhase::PushingHistory { .. })))
+            .count();
+        assert_eq!(
+            batches, expected_batches,
+            "{configured:?}: 4 commits are pushed, with a batch size of at least 1"
+        );
+    }
+}
+
+#[test]
+fn batches_are_pushed_oldest_first_with_the_target_in_the_last() {
+    let suite = Suite::default();
+    let user = suite.sign_in();
+    let (server, _server_tmp) = empty_bare_repository();
+    let case = suite.new_case();
+    linear_history(case.ctx.repo(), 3);
+    gitbutler_testsupport::virtual_branches::set_test_target(&case.ctx).unwrap();
+    let case = link_to(&suite, case, &server);
+    suite
+        .projects
+        .update(&UpdateRequest {
+            id: case.project.id,
+            code_push_batch_size: Some(2),
+            ..Default::default()
+        })
+        .unwrap();
+    let case = case.refresh(&suite);
+
+    let transport = FakeTransport::default();
+    push_repo_via(
+        &case.ctx,
+        &user,
+        &suite.projects,
+        &PushBudget::default(),
+        &CancellationToken::new(),
+        &transport,
+        &|_event| {},
+    )
+    .unwrap();
+
+    let target = VirtualBranchesHandle::new(case.project.gb_dir())
+        .get_default_target()
+        .unwrap()
+        .sha;
+    let first_batch = {
+        let repo = case.ctx.repo();
+        let target = repo.find_commit(target).unwrap();
+        target.parent(0).unwrap().parent_id(0).unwrap()
+    };
+    let id = case.project.id;
+    let force = if *case.project.allow_force_push {
+        "+"
+    } else {
+        ""
+    };
+    let received = transport.received();
+    assert_eq!(
+        received[..3],
+        [
+            vec![],
+            vec![format!("+{first_batch}:refs/push-tmp/{id}-batch-1-of-2")],
+            vec![
+                format!("+{target}:refs/push-tmp/{id}-batch-2-of-2"),
+                format!("{force}{target}:refs/{id}"),
+            ],
+        ],
+        "the first push of a project checks for permission, then sends 2 commits at a time"
+    );
+    assert!(
+        received[3..]
+            .iter()
+            .flatten()
+            .all(|ref_spec| !ref_spec.contains("refs/push-tmp/")),
+        "the refs are pushed afterwards: {received:?}"
+    );
+    assert!(
+        server.references().unwrap().next().is_none(),
+        "the server isn't contacted for pushing"
+    );
+}
+
+#[test]
+fn pushes_stay_within_the_upload_rate_limit() {
+    let suite = Suite::default();
+    let user = suite.sign_in();
+    let (server, _server_tmp) = empty_bare_repository();
+    let case = suite.new_case();
+    const PAYLOAD_BYTES: usize = 8 * 1024;
+    {
+        let repo = case.ctx.repo();
+        // Pseudo-random bytes don't compress, so the pack is at least as large as they are.
+        let mut state = 1u32;
+        let payload: Vec<u8> = (0..PAYLOAD_BYTES)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                (state >> 16) as u8
+            })
+            .collect();
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        let mut tree = repo.treebuilder(Some(&head.tree().unwrap())).unwrap();
+        tree.insert("payload", repo.blob(&payload).unwrap(), 0o100644)
+            .unwrap();
+        let tree = repo.find_tree(tree.write().unwrap()).unwrap();
+        let signature = git2::Signature::now("test", "test@example.com").unwrap();
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            "payload",
+            &tree,
+            &[&head],
+        )
+        .unwrap();
+    }
+    gitbutler_testsupport::virtual_branches::set_test_target(&case.ctx).unwrap();
+    let case = link_to(&suite, case, &server);
+    let bytes_per_second = 4 * 1024;
+    suite
+        .projects
+        .update(&UpdateRequest {
+            id: case.project.id,
+            code_push_max_bytes_per_second: Some(bytes_per_second),
+            ..Default::default()
+        })
+        .unwrap();
+    let case = case.refresh(&suite);
+
+    let sent = RefCell::new(0);
+    let started = Instant::now();
+    push_repo(
+        &case.ctx,
+        &user,
+        &suite.projects,
+        &PushBudget::default(),
+        &CancellationToken::new(),
+        &|event| {
+            if let Event::Transferred { bytes, .. } = event {
+                *sent.borrow_mut() += bytes;
+            }
+        },
+    )
+    .unwrap();
+    let elapsed = started.elapsed();
+    let sent = sent.into_inner();
+    assert!(sent >= PAYLOAD_BYTES as u64, "sent {sent} bytes");
+    assert!(
+        elapsed >= Duration::from_secs_f64(sent as f64 / bytes_per_second as f64),
+        "sending {sent} bytes at {bytes_per_second} bytes per second takes longer than {elapsed:?}"
+    );
+}
+
+#[test]
+fn large_pushes_complete_over_multiple_budgeted_pushes() {
+    let suite = Suite::default();
+    let user = suite.sign_in();
+    let (server, _server_tmp) = empty_bare_repository();
+    let case = suite.new_case();
+    let branches = ["refs/heads/a", "refs/heads/b", "refs/heads/c"];
+    {
+        let repo = case.ctx.repo();
+        linear_history(repo, 30);
+        let head = repo.head().unwrap().target().unwrap();
+        for branch in branches {
+            repo.reference(branch, head, false, "").unwrap();
+        }
+    }
+    gitbutler_testsupport::virtual_branches::set_test_target(&case.ctx).unwrap();
+    let mut case = link_to(&suite, case, &server);
+
+    let budget = PushBudget {
+        max_batches: Some(1),
+        max_refs: Some(1),
+        max_duration: None,
+    };
+    let mut pushes = 0;
+    loop {
+        pushes += 1;
+        assert!(pushes <= 50, "the push never completes");
+        let events = RefCell::new(Vec::new());
+        let outcome = push_repo(
+            &case.ctx,
+            &user,
+            &suite.projects,
+            &budget,
+            &CancellationToken::new(),
+            &|event| events.borrow_mut().push(event),
+        )
+        .unwrap();
+        let events = events.into_inner();
+        if outcome == PushOutcome::Complete {
+            assert_eq!(events.last(), Some(&Event::Phase(PushPhase::Done)));
+            break;
+        }
+        assert_eq!(events.last(), Some(&Event::Paused));
+        case = case.refresh(&suite);
+    }
+
+    assert!(
+        pushes > 4,
+        "history and refs are spread over multiple pushes"
+    );
+    assert!(server
+        .find_reference(&format!("refs/{}", case.project.id))
+        .is_ok());
+    for branch in branches.into_iter().chain(["refs/heads/master"]) {
+        assert!(server.find_reference(branch).is_ok(), "{branch} was pushed");
+    }
+    let case = case.refresh(&suite);
+    assert_eq!(
+        case.project
+            .gitbutler_code_push_state
+            .and_then(|state| state.refs_cursor),
+        None,
+        "the cursor is cleared once all refs are pushed"
+    );
+}
+
+#[test]
+fn push_refs_are_categorized_by_kind() {
+    let suite = Suite::default();
+    let case = suite.new_case();
+    assert_eq!(
+        categorized_push_refs(&case.ctx).unwrap(),
+        PushRefs::default(),
+        "without target, nothing is pushed"
+    );
+    gitbutler_testsupport::virtual_branches::set_test_target(&case.ctx).unwrap();
+    {
+        let repo = case.ctx.repo();
+        let head = repo.head().unwrap().target().unwrap();
+        for name in [
+            "refs/heads/feature",
+            "refs/remotes/origin/feature",
+            "refs/gitbutler/feature",
+            "refs/tags/v1",
+            "refs/notes/commits",
+            // This isn't pushed.
+            "refs/remotes/gitbutler/feature",
+        ] {
+            repo.reference(name, head, false, "").unwrap();
+        }
+    }
+
+    let refs = categorized_push_refs(&case.ctx).unwrap();
+    let contains = |refs: &[Refname], name: &str| refs.iter().any(|r| r.to_string() == name);
+    assert!(contains(&refs.local, "refs/heads/feature"), "{refs:?}");
+    assert!(
+        contains(&refs.remote, "refs/remotes/origin/feature"),
+        "{refs:?}"
+    );
+    assert!(
+        contains(&refs.r#virtual, "refs/gitbutler/feature"),
+        "{refs:?}"
+    );
+    assert_eq!(refs.tags, vec!["refs/tags/v1".parse::<Refname>().unwrap()]);
+    assert_eq!(
+        refs.notes,
+        vec!["refs/notes/commits".parse::<Refname>().unwrap()]
+    );
+    assert!(!contains(&refs.remote, "refs/remotes/gitbutler/feature"));
+    assert!(refs.local.iter().all(|r| matches!(r, Refname::Local(_))));
+    assert!(refs.remote.iter().all(|r| matches!(r, Refname::Remote(_))));
+}
+
+#[test]
+fn diverged_targets_are_explained_unless_force_pushes_are_allowed() {
+    let suite = Suite::default();
+    let user = suite.sign_in();
+    let (server, _server_tmp) = empty_bare_repository();
+    let case = suite.new_case();
+    gitbutler_testsupport::virtual_branches::set_test_target(&case.ctx).unwrap();
+    let case = link_to(&suite, case, &server);
+    suite
+        .projects
+        .update(&UpdateRequest {
+            id: case.project.id,
+            allow_force_push: Some(false),
+            ..Default::default()
+        })
+        .unwrap();
+    let case = case.refresh(&suite);
+    let push = |case: &gitbutler_testsupport::Case| {
+        push_repo(
+            &case.ctx,
+            &user,
+            &suite.projects,
+            &PushBudget::default(),
+            &CancellationToken::new(),
+            &|_event| {},
+        )
+    };
+    push(&case).unwrap();
+
+    // The target is replaced by a commit of unrelated history, like after a force push to it.
+    let vb_state = VirtualBranchesHandle::new(case.project.gb_dir());
+    let mut target = vb_state.get_default_target().unwrap();
+    let pushed = target.sha;
+    {
+        let repo = case.ctx.repo();
+        let signature = git2::Signature::now("test", "test@example.com").unwrap();
+        let tree = repo.find_commit(target.sha).unwrap().tree().unwrap();
+        target.sha = repo
+            .commit(None, &signature, &signature, "rewritten", &tree, &[])
+            .unwrap();
+    }
+    vb_state.set_default_target(target).unwrap();
+    let case = case.refresh(&suite);
+
+    let err = push(&case).unwrap_err();
+    assert!(
+        matches!(
+            err.downcast_ref::<RemoteError>(),
+            Some(RemoteError::NonFastForward { .. })
+        ),
+        "{err:?}"
+    );
+    assert!(
+        format!("{err:#}").contains("The server has a newer state of project"),
+        "{err:#}"
+    );
+    assert_eq!(
+        server
+            .refname_to_id(&format!("refs/{}", case.project.id))
+            .unwrap(),
+        pushed,
+        "the target on the server is left alone"
+    );
+}
+
+#[test]
+fn pushes_sooner_than_the_minimum_interval_are_skipped_unless_the_target_moved() {
+    let suite = Suite::default();
+    let user = suite.sign_in();
+    let (server, _server_tmp) = empty_bare_repository();
+    let case = suite.new_case();
+    gitbutler_testsupport::virtual_branches::set_test_target(&case.ctx).unwrap();
+    let case = link_to(&suite, case, &server);
+    suite
+        .projects
+        .update(&UpdateRequest {
+            id: case.project.id,
+            code_push_min_interval_seconds: Some(60 * 60),
+            ..Default::default()
+        })
+        .unwrap();
+    let case = case.refresh(&suite);
+    let push = |case: &gitbutler_testsupport::Case| {
+        let events = RefCell::new(Vec::new());
+        push_repo(
+            &case.ctx,
+            &user,
+            &suite.projects,
+            &PushBudget::default(),
+            &CancellationToken::new(),
+            &|event| events.borrow_mut().push(event),
+        )
+        .unwrap();
+        events.into_inner()
+    };
+    assert!(
+        push(&case).contains(&Event::Phase(PushPhase::Done)),
+        "the first push goes through"
+    );
+    let case = case.refresh(&suite);
+    assert_eq!(
+        push(&case),
+        [
+            Event::Phase(PushPhase::ReadingTarget),
+            Event::SkippedTooSoon
+        ],
+        "the server isn't contacted again"
+    );
+
+    let vb_state = VirtualBranchesHandle::new(case.project.gb_dir());
+    let mut target = vb_state.get_default_target().unwrap();
+    {
+        let repo = case.ctx.repo();
+        let signature = git2::Signature::now("test", "test@example.com").unwrap();
+        let parent = repo.find_commit(target.sha).unwrap();
+        target.sha = repo
+            .commit(
+                None,
+                &signature,
+                &signature,
+                "new",
+                &parent.tree().unwrap(),
+                &[&parent],
+            )
+            .unwrap();
+    }
+    vb_state.set_default_target(target.clone()).unwrap();
+    let case = case.refresh(&suite);
+    assert!(
+        push(&case).contains(&Event::Phase(PushPhase::Done)),
+        "target changes are pushed right away"
+    );
+    assert_eq!(
+        server
+            .refname_to_id(&format!("refs/{}", case.project.id))
+            .unwrap(),
+        target.sha
+    );
+}
+
+#[test]
+fn syncing_now_ignores_the_minimum_interval_and_reports_what_it_pushed() {
+    let suite = Suite::default();
+    let user = suite.sign_in();
+    let (server, _server_tmp) = empty_bare_repository();
+    let case = suite.new_case();
+    gitbutler_testsupport::virtual_branches::set_test_target(&case.ctx).unwrap();
+    let case = link_to(&suite, case, &server);
+    suite
+        .projects
+        .update(&UpdateRequest {
+            id: case.project.id,
+            code_push_min_interval_seconds: Some(60 * 60),
+            ..Default::default()
+        })
+        .unwrap();
+    let case = case.refresh(&suite);
+    let sync = |case: &gitbutler_testsupport::Case| {
+        sync_now(
+            &case.ctx,
+            &user,
+            &suite.projects,
+            &GitTransport,
+            &CancellationToken::new(),
+            &|_event| {},
+        )
+        .unwrap()
+    };
+    assert!(matches!(sync(&case), SyncOutcome::Pushed { .. }));
+
+    {
+        let repo = case.ctx.repo();
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        repo.tag_lightweight("v1", head.as_object(), false).unwrap();
+    }
+    let case = case.refresh(&suite);
+    assert_eq!(
+        sync(&case),
+        SyncOutcome::Pushed { refs: 1 },
+        "the push goes through even though the last one was just now"
+    );
+    assert!(server.find_reference("refs/tags/v1").is_ok());
+    assert_eq!(
+        sync(&case.refresh(&suite)),
+        SyncOutcome::UpToDate,
+        "the server has everything already"
+    );
+}
+
+#[test]
+fn targets_the_server_did_not_apply_are_errors_if_verified() {
+    let suite = Suite::default();
+    let user = suite.sign_in();
+    let (server, _server_tmp) = empty_bare_repository();
+    let case = suite.new_case();
+    gitbutler_testsupport::virtual_branches::set_test_target(&case.ctx).unwrap();
+    let case = link_to(&suite, case, &server);
+    let mut settings = AppSettings::default();
+    settings.code_push.verify_target_push = true;
+    let ctx = CommandContext::open(&case.project, settings.clone()).unwrap();
+    push_repo(
+        &ctx,
+        &user,
+        &suite.projects,
+        &PushBudget::default(),
+        &CancellationToken::new(),
+        &|_event| {},
+    )
+    .unwrap();
+    let refname = format!("refs/{}", case.project.id);
+    let pushed = server.refname_to_id(&refname).unwrap();
+
+    let vb_state = VirtualBranchesHandle::new(case.project.gb_dir());
+    let mut target = vb_state.get_default_target().unwrap();
+    {
+        let repo = case.ctx.repo();
+        let signature = git2::Signature::now("test", "test@example.com").unwrap();
+        let parent = repo.find_commit(target.sha).unwrap();
+        target.sha = repo
+            .commit(
+                None,
+                &signature,
+                &signature,
+                "new",
+                &parent.tree().unwrap(),
+                &[&parent],
+            )
+            .unwrap();
+    }
+    vb_state.set_default_target(target).unwrap();
+
+    // The server accepts the push of the target, but then puts its ref back.
+    let case = case.refresh(&suite);
+    let ctx = CommandContext::open(&case.project, settings).unwrap();
+    let pushing_target = std::cell::Cell::new(false);
+    let err = push_repo(
+        &ctx,
+        &user,
+        &suite.projects,
+        &PushBudget::default(),
+        &CancellationToken::new(),
+        &|event| match event {
+            Event::Phase(PushPhase::PushingTarget) => pushing_target.set(true),
+            Event::Transferred { .. } if pushing_target.get() => {
+                server.reference(&refname, pushed, true, "stale").unwrap();
+            }
+            _ => {}
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<Code>(),
+        Some(&Code::Validation),
+        "{err:#}"
+    );
+    assert!(format!("{err:#}").contains("but its ref points to commit"));
+
+    let case = case.refresh(&suite);
+    assert_eq!(
+        case.project.gitbutler_code_push_state.unwrap().id,
+        pushed,
+        "the target isn't recorded as pushed, so the next push sends it again"
+    );
+}
+
+#[test]
+fn commits_left_to_push_shrink_with_each_push() {
+    let suite = Suite::default();
+    let user = suite.sign_in();
+    let (server, _server_tmp) = empty_bare_repository();
+    let case = suite.new_case();
+    linear_history(case.ctx.repo(), 5);
+    assert_eq!(
+        commits_left_to_push(&case.ctx).unwrap(),
+        0,
+        "without target, nothing is pushed"
+    );
+    gitbutler_testsupport::virtual_branches::set_test_target(&case.ctx).unwrap();
+    let case = link_to(&suite, case, &server);
+    suite
+        .projects
+        .update(&UpdateRequest {
+            id: case.project.id,
+            code_push_batch_size: Some(2),
+            ..Default::default()
+        })
+        .unwrap();
+    let case = case.refresh(&suite);
+    let all = commits_left_to_push(&case.ctx).unwrap();
+    assert!(all > 5, "the whole history is left without a previous push");
+
+    let budget = PushBudget {
+        max_batches: Some(1),
+        ..Default::default()
+    };
+    let push = |case: &gitbutler_testsupport::Case, budget: &PushBudget| {
+        push_repo(
+            &case.ctx,
+            &user,
+            &suite.projects,
+            budget,
+            &CancellationToken::new(),
+            &|_event| {},
+        )
+        .unwrap()
+    };
+    assert_eq!(push(&case, &budget), PushOutcome::Paused);
+    let case = case.refresh(&suite);
+    let left = commits_left_to_push(&case.ctx).unwrap();
+    assert!(0 < left && left < all, "{left} of {all} commits are left");
+
+    assert_eq!(push(&case, &PushBudget::default()), PushOutcome::Complete);
+    let case = case.refresh(&suite);
+    assert_eq!(commits_left_to_push(&case.ctx).unwrap(), 0);
+}
+
+#[test]
+fn pushes_resume_after_the_last_pushed_batch_if_its_state_was_lost() {
+    let suite = Suite::default();
+    let user = suite.sign_in();
+    let (server, _server_tmp) = empty_bare_repository();
+    let case = suite.new_case();
+    linear_history(case.ctx.repo(), 7);
+    gitbutler_testsupport::virtual_branches::set_test_target(&case.ctx).unwrap();
+    let case = link_to(&suite, case, &server);
+    suite
+        .projects
+        .update(&UpdateRequest {
+            id: case.project.id,
+            code_push_batch_size: Some(2),
+            ..Default::default()
+        })
+        .unwrap();
+    let case = case.refresh(&suite);
+    let push = |case: &gitbutler_testsupport::Case, budget: &PushBudget| {
+        let events = RefCell::new(Vec::new());
+        let outcome = push_repo(
+            &case.ctx,
+            &user,
+            &suite.projects,
+            budget,
+            &CancellationToken::new(),
+            &|event| events.borrow_mut().push(event),
+        )
+        .unwrap();
+        let commits = events
+            .into_inner()
+            .into_iter()
+            .find_map(|event| match event {
+                Event::PushStarted { commits, .. } => Some(commits),
+                _ => None,
+            });
+        (outcome, commits)
+    };
+    let one_batch = PushBudget {
+        max_batches: Some(1),
+        ..Default::default()
+    };
+    assert_eq!(push(&case, &one_batch).0, PushOutcome::Paused);
+    let case = case.refresh(&suite);
+    let first_state = case.project.gitbutler_code_push_state.clone().unwrap();
+    assert_eq!(push(&case, &one_batch).0, PushOutcome::Paused);
+
+    // As if the app was killed before the state of the second batch was written.
+    suite
+        .projects
+        .update(&UpdateRequest {
+            id: case.project.id,
+            gitbutler_code_push_state: Some(first_state),
+            ..Default::default()
+        })
+        .unwrap();
+    let case = case.refresh(&suite);
+    let left_by_state = commits_left_to_push(&case.ctx).unwrap();
+    let (outcome, commits) = push(&case, &PushBudget::default());
+    assert_eq!(outcome, PushOutcome::Complete);
+    assert!(
+        commits.is_some_and(|commits| commits < left_by_state),
+        "the second batch isn't pushed again: {commits:?} of {left_by_state}"
+    );
+    assert!(server
+        .find_reference(&format!("refs/{}", case.project.id))
+        .is_ok());
+}
+
+#[test]
+fn cancelled_pushes_stop_after_the_current_batch() {
+    let suite = Suite::default();
+    let user = suite.sign_in();
+    let (server, _server_tmp) = empty_bare_repository();
+    let case = suite.new_case();
+    {
+        let repo = case.ctx.repo();
+        linear_history(repo, 5);
+        repo.reference(
+            "refs/heads/feature",
+            repo.head().unwrap().target().unwrap(),
+            false,
+            "",
+        )
+        .unwrap();
+    }
+    gitbutler_testsupport::virtual_branches::set_test_target(&case.ctx).unwrap();
+    let case = link_to(&suite, case, &server);
+    suite
+        .projects
+        .update(&UpdateRequest {
+            id: case.project.id,
+            code_push_batch_size: Some(2),
+            ..Default::default()
+        })
+        .unwrap();
+    let case = case.refresh(&suite);
+
+    let cancellation = CancellationToken::new();
+    let events = RefCell::new(Vec::new());
+    let outcome = push_repo(
+        &case.ctx,
+        &user,
+        &suite.projects,
+        &PushBudget::default(),
+        &cancellation,
+        &|event| {
+            if matches!(event, Event::BatchPushed { .. }) {
+                cancellation.cancel();
+            }
+            events.borrow_mut().push(event)
+        },
+    )
+    .unwrap();
+    assert_eq!(outcome, PushOutcome::Cancelled);
+    let events = events.into_inner();
+    assert_eq!(events.last(), Some(&Event::Cancelled));
+    assert_eq!(
+        events
+            .iter()
+            .filter(|event| matches!(event, Event::BatchPushed { .. }))
+            .count(),
+        1,
+        "no batch is pushed after cancelling"
+    );
+    assert!(
+        !events.contains(&Event::Phase(PushPhase::PushingRefs)),
+        "refs aren't pushed once cancelled"
+    );
+    assert!(server.find_reference("refs/heads/feature").is_err());
+    assert!(server
+        .find_reference(&format!("refs/{}", case.project.id))
+        .is_err());
+
+    let case = case.refresh(&suite);
+    let state = case
+        .project
+        .gitbutler_code_push_state
+        .expect("the pushed batch is stored");
+    assert!(
+        server.find_commit(state.id).is_ok(),
+        "the stored state points to the last pushed batch"
+    );
+    assert_ne!(
+        state.id,
+        case.ctx.repo().refname_to_id("refs/heads/master").unwrap(),
+        "the target isn't pushed yet"
+    );
+
+    let case = case.refresh(&suite);
+    let outcome = push_repo(
+        &case.ctx,
+        &user,
+        &suite.projects,
+        &PushBudget::default(),
+        &CancellationToken::new(),
+        &|_event| {},
+    )
+    .unwrap();
+    assert_eq!(outcome, PushOutcome::Complete, "the next push resumes");
+    assert!(server.find_reference("refs/heads/feature").is_ok());
+}
+
+#[test]
+fn failed_pushes_report_the_batches_they_pushed() {
+    let suite = Suite::default();
+    let user = suite.sign_in();
+    let (server, _server_tmp) = empty_bare_repository();
+    let case = suite.new_case();
+    linear_history(case.ctx.repo(), 8);
+    gitbutler_testsupport::virtual_branches::set_test_target(&case.ctx).unwrap();
+    let case = link_to(&suite, case, &server);
+    suite
+        .projects
+        .update(&UpdateRequest {
+            id: case.project.id,
+            code_push_batch_size: Some(2),
+            ..Default::default()
+        })
+        .unwrap();
+    let case = case.refresh(&suite);
+
+    // The server disappears once the third batch is pushed, so pushing the fourth one fails.
+    let moved_server = server.path().with_extension("moved");
+    let events = RefCell::new(Vec::new());
+    push_repo(
+        &case.ctx,
+        &user,
+        &suite.projects,
+        &PushBudget::default(),
+        &CancellationToken::new(),
+        &|event| {
+            if matches!(event, Event::BatchPushed { completed: 3, .. }) {
+                std::fs::rename(server.path(), &moved_server).unwrap();
+            }
+            events.borrow_mut().push(event)
+        },
+    )
+    .unwrap_err();
+    std::fs::rename(&moved_server, server.path()).unwrap();
+
+    let reported = events
+        .into_inner()
+        .into_iter()
+        .find_map(|event| match event {
+            Event::HistoryPushEnded {
+                completed,
+                total,
+                last_pushed,
+            } => Some((completed, total, last_pushed)),
+            _ => None,
+        });
+    let Some((completed, total, Some(last_pushed))) = reported else {
+        panic!("the progress is reported even though the push failed: {reported:?}");
+    };
+    assert_eq!(completed, 3);
+    assert!(total > completed, "{total}");
+
+    let case = case.refresh(&suite);
+    let state = case.project.gitbutler_code_push_state.unwrap();
+    assert_eq!(
+        state.id, last_pushed,
+        "the stored state agrees with the reported progress"
+    );
+    assert!(server.find_commit(last_pushed).is_ok());
+}
+
+#[test]
+fn mirrors_are_pushed_even_if_one_of_them_fails() {
+    let suite = Suite::default();
+    let user = suite.sign_in();
+    let (server, _server_tmp) = empty_bare_repository();
+    let (mirror, _mirror_tmp) = empty_bare_repository();
+    let case = suite.new_case();
+    {
+        let repo = case.ctx.repo();
+        let head = repo.head().unwrap().target().unwrap();
+        repo.reference("refs/heads/feature", head, false, "")
+            .unwrap();
+    }
+    gitbutler_testsupport::virtual_branches::set_test_target(&case.ctx).unwrap();
+    let case = link_to(&suite, case, &server);
+    let unresponsive = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let unresponsive_url = format!("http://{}/code.git", unresponsive.local_addr().unwrap());
+    let mirror_url = mirror.path().to_str().unwrap().to_owned();
+    suite
+        .projects
+        .update(&UpdateRequest {
+            id: case.project.id,
+            code_push_mirrors: Some(
+                [&unresponsive_url, &mirror_url]
+                    .map(|url| CodePushMirror {
+                        url: url.clone(),
+                        state: None,
+                    })
+                    .into(),
+            ),
+            ..Default::default()
+        })
+        .unwrap();
+    let case = case.refresh(&suite);
+    let mut settings = AppSettings::default();
+    settings.code_push.push_timeout_seconds = 1;
+    settings.code_push.network_retries = 0;
+    let ctx = CommandContext::open(&case.project, settings).unwrap();
+
+    let events = RefCell::new(Vec::new());
+    push_repo(
+        &ctx,
+        &user,
+        &suite.projects,
+        &PushBudget::default(),
+        &CancellationToken::new(),
+        &|event| events.borrow_mut().push(event),
+    )
+    .unwrap();
+    let mirrored: Vec<_> = events
+        .into_inner()
+        .into_iter()
+        .filter_map(|event| match event {
+            Event::MirrorPushed { url, error } => Some((url, error.is_none())),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        mirrored,
+        [
+            (unresponsive_url.clone(), false),
+            (mirror_url.clone(), true)
+        ],
+        "the mirror after the failing one is still pushed"
+    );
+    for server in [&server, &mirror] {
+        assert!(server.find_reference("refs/heads/feature").is_ok());
+        assert!(server
+            .find_reference(&format!("refs/{}", case.project.id))
+            .is_ok());
+    }
+
+    let case = case.refresh(&suite);
+    let state = |url: &str| {
+        case.project
+            .code_push_mirrors
+            .iter()
+            .find(|mirror| mirror.url == url)
+            .unwrap()
+            .state
+            .clone()
+    };
+    assert!(state(&unresponsive_url).is_none());
+    assert_eq!(
+        state(&mirror_url).map(|state| state.id),
+        case.project.gitbutler_code_push_state.map(|state| state.id),
+        "each server has its own state"
+    );
+}
+
+#[test]
+fn push_permission_is_granted_for_writable_servers() {
+    let suite = Suite::default();
+    let user = suite.sign_in();
+    let (server, _server_tmp) = empty_bare_repository();
+    let case = link_to(&suite, suite.new_case(), &server);
+
+    check_push_permission(&case.ctx, &user).unwrap();
+    assert_eq!(
+        server.references().unwrap().count(),
+        0,
+        "checking doesn't push anything"
+    );
+}
+
+#[test]
+fn failed_pushes_are_recorded_until_a_push_succeeds() {
+    let suite = Suite::default();
+    let user = suite.sign_in();
+    let (server, _server_tmp) = empty_bare_repository();
+    let case = suite.new_case();
+    gitbutler_testsupport::virtual_branches::set_test_target(&case.ctx).unwrap();
+    let case = link_to(&suite, case, &server);
+    let transport = FakeTransport::default();
+    let push = || {
+        push_repo_via(
+            &case.ctx,
+            &user,
+            &suite.projects,
+            &PushBudget::default(),
+            &CancellationToken::new(),
+            &transport,
+            &|_event| {},
+        )
+    };
+    let last_push_error = || suite.projects.get(case.project.id).unwrap().last_push_error;
+
+    transport.fail_next(FakeFailure::Network);
+    push().unwrap_err();
+    assert_eq!(
+        last_push_error().map(|err| err.kind),
+        Some(PushErrorKind::Network)
+    );
+
+    transport.fail_next(FakeFailure::Auth);
+    let err = push().unwrap_err();
+    let recorded = last_push_error().unwrap();
+    assert_eq!(
+        recorded.kind,
+        PushErrorKind::Auth,
+        "the latest error is kept"
+    );
+    assert_eq!(recorded.message, format!("{err:#}"));
+
+    push().unwrap();
+    assert_eq!(last_push_error(), None, "successful pushes clear the error");
+}
+
+#[test]
+fn unreachable_servers_defer_the_push() {
+    let suite = Suite::default();
+    let user = suite.sign_in();
+    let case = suite.new_case();
+    gitbutler_testsupport::virtual_branches::set_test_target(&case.ctx).unwrap();
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/code.git", listener.local_addr().unwrap());
+    suite
+        .projects
+        .update(&UpdateRequest {
+            id: case.project.id,
+            api: Some(api_project(Some(url))),
+            ..Default::default()
+        })
+        .unwrap();
+    let case = case.refresh(&suite);
+    let mut settings = AppSettings::default();
+    settings.code_push.push_timeout_seconds = 1;
+    let ctx = CommandContext::open(&case.project, settings).unwrap();
+
+    let events = RefCell::new(Vec::new());
+    let err = push_repo(
+        &ctx,
+        &user,
+        &suite.projects,
+        &PushBudget::default(),
+        &CancellationToken::new(),
+        &|event| events.borrow_mut().push(event),
+    )
+    .unwrap_err();
+    assert!(
+        matches!(
+            err.downcast_ref::<RemoteError>(),
+            Some(RemoteError::Network(_))
+        ),
+        "{err:?}"
+    );
+    assert!(
+        matches!(
+            events.into_inner().last(),
+            Some(Event::SyncDeferred { reason }) if reason.contains("network failed")
+        ),
+        "the frontend learns that the push is tried again later"
+    );
+}
+
+#[test]
+fn pushes_go_through_the_configured_proxy() {
+    use std::io::Read;
+
+    let suite = Suite::default();
+    let user = suite.sign_in();
+    let case = suite.new_case();
+    gitbutler_testsupport::virtual_branches::set_test_target(&case.ctx).unwrap();
+    let proxy = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    suite
+        .projects
+        .update(&UpdateRequest {
+            id: case.project.id,
+            api: Some(api_project(Some(
+                "http://gitbutler.invalid/code.git".to_owned(),
+            ))),
+            code_push_proxy_url: Some(format!("http://{}", proxy.local_addr().unwrap())),
+            ..Default::default()
+        })
+        .unwrap();
+    let case = case.refresh(&suite);
+    let mut settings = AppSettings::default();
+    settings.code_push.push_timeout_seconds = 1;
+    let ctx = CommandContext::open(&case.project, settings).unwrap();
+
+    push_repo(
+        &ctx,
+        &user,
+        &suite.projects,
+        &PushBudget::default(),
+        &CancellationToken::new(),
+        &|_| {},
+    )
+    .unwrap_err();
+
+    let (mut conn, _) = proxy.accept().unwrap();
+    conn.set_read_timeout(Some(Duration::from_secs(1))).unwrap();
+    let mut request = vec![0; 4096];
+    let len = conn.read(&mut request).unwrap();
+    assert!(
+        String::from_utf8_lossy(&request[..len]).contains("gitbutler.invalid"),
+        "the server is reached through the proxy instead of directly"
+    );
+}
+
+#[test]
+fn malformed_proxies_are_configuration_errors() {
+    let suite = Suite::default();
+    let user = suite.sign_in();
+    let case = suite.new_case();
+    let (server, _server_tmp) = empty_bare_repository();
+    let case = link_to(&suite, case, &server);
+    suite
+        .projects
+        .update(&UpdateRequest {
+            id: case.project.id,
+            code_push_proxy_url: Some("no proxy".to_owned()),
+            ..Default::default()
+        })
+        .unwrap();
+    let case = case.refresh(&suite);
+
+    let err = check_push_permission(&case.ctx, &user).unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<Code>(),
+        Some(&Code::Validation),
+        "{err:#}"
+    );
+
+    suite
+        .projects
+        .update(&UpdateRequest {
+            id: case.project.id,
+            unset_code_push_proxy_url: true,
+            ..Default::default()
+        })
+        .unwrap();
+    let case = case.refresh(&suite);
+    check_push_permission(&case.ctx, &user).expect("the server is reached directly again");
+}
+
+mod push_virtual_branch {
+    use gitbutler_branch::BranchCreateRequest;
+
+    use super::*;
+
+    #[test]
+    fn pushes_only_the_branch() {
+        let suite = Suite::default();
+        let user = suite.sign_in();
+        let (server, _server_tmp) = empty_bare_repository();
+        let case = suite.new_case();
+        gitbutler_testsupport::virtual_branches::set_test_target(&case.ctx).unwrap();
+        let case = link_to(&suite, case, &server);
+        let stack = gitbutler_branch_actions::create_virtual_branch(
+            &case.ctx,
+            &BranchCreateRequest::default(),
+        )
+        .unwrap();
+        std::fs::write(case.project.path.join("file.txt"), "content").unwrap();
+        let commit =
+            gitbutler_branch_actions::create_commit(&case.ctx, stack.id, "shared", None).unwrap();
+
+        assert!(push_virtual_branch(&case.ctx, &user, stack.id).unwrap());
+        assert_eq!(
+            server
+                .find_reference("refs/gitbutler/Lane")
+                .unwrap()
+                .target(),
+            Some(commit)
+        );
+        assert!(server.find_reference("refs/heads/master").is_err());
+    }
+
+    #[test]
+    fn branches_without_commits_are_not_pushed() {
+        let suite = Suite::default();
+        let user = suite.sign_in();
+        let (server, _server_tmp) = empty_bare_repository();
+        let case = suite.new_case();
+        gitbutler_testsupport::virtual_branches::set_test_target(&case.ctx).unwrap();
+        let case = link_to(&suite, case, &server);
+        let stack = gitbutler_branch_actions::create_virtual_branch(
+            &case.ctx,
+            &BranchCreateRequest::default(),
+        )
+        .unwrap();
+
+        assert!(!push_virtual_branch(&case.ctx, &user, stack.id).unwrap());
+        assert!(server.find_reference("refs/gitbutler/Lane").is_err());
+    }
+}
+
+mod fetch_remote_branches {
+    use super::*;
+
+    /// Create an empty-tree commit on the `server` and point `refname` to it.
+    fn server_commit(server: &git2::Repository, refname: &str) -> git2::Oid {
+        let signature = git2::Signature::now("collaborator", "collaborator@example.com").unwrap();
+        let tree = server
+            .find_tree(server.treebuilder(None).unwrap().write().unwrap())
+            .unwrap();
+        server
+            .commit(
+                Some(refname),
+                &signature,
+                &signature,
+                "pushed by a collaborator",
+                &tree,
+                &[],
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn branches_are_fetched_with_conflicts_against_local_branches() {
+        let suite = Suite::default();
+        let user = suite.sign_in();
+        let (server, _server_tmp) = empty_bare_repository();
+        let feature = server_commit(&server, "refs/heads/feature");
+        let master = server_commit(&server, "refs/heads/master");
+        let case = link_to(&suite, suite.new_case(), &server);
+
+        let branches = fetch_remote_branches(&case.ctx, &user).unwrap();
+        assert_eq!(
+            branches,
+            vec![
+                ServerBranch {
+                    name: RemoteRefname::new("gitbutler", "feature"),
+                    sha: feature,
+                    conflicts_with: None,
+                },
+                ServerBranch {
+                    name: RemoteRefname::new("gitbutler", "master"),
+                    sha: master,
+                    conflicts_with: Some(LocalRefname::new("master", None)),
+                },
+            ]
+        );
+        assert_eq!(
+            case.ctx
+                .repo()
+                .find_reference("refs/remotes/gitbutler/feature")
+                .unwrap()
+                .target(),
+            Some(feature)
+        );
+    }
+
+    #[test]
+    fn branches_deleted_on_the_server_are_removed() {
+        let suite = Suite::default();
+        let user = suite.sign_in();
+        let (server, _server_tmp) = empty_bare_repository();
+        server_commit(&server, "refs/heads/feature");
+        let case = link_to(&suite, suite.new_case(), &server);
+        assert_eq!(fetch_remote_branches(&case.ctx, &user).unwrap().len(), 1);
+
+        server
+            .find_reference("refs/heads/feature")
+            .unwrap()
+            .delete()
+            .unwrap();
+        assert!(fetch_remote_branches(&case.ctx, &user).unwrap().is_empty());
+        assert!(case
+            .ctx
+            .repo()
+            .find_reference("refs/remotes/gitbutler/feature")
+            .is_err());
+    }
+    #[test]
+    fn branches_are_fetched_through_the_transport() {
+        let suite = Suite::default();
+        let user = suite.sign_in();
+        let (server, _server_tmp) = empty_bare_repository();
+        server_commit(&server, "refs/heads/feature");
+        let case = link_to(&suite, suite.new_case(), &server);
+        let transport = FakeTransport::default();
+
+        let branches = fetch_remote_branches_via(&case.ctx, &user, &transport).unwrap();
+        assert!(branches.is_empty(), "the fake server has no branches");
+        assert_eq!(
+            transport.fetched(),
+            [vec!["+refs/heads/*:refs/remotes/gitbutler/*".to_owned()]]
+        );
+        assert!(
+            case.ctx
+                .repo()
+                .find_reference("refs/remotes/gitbutler/feature")
+                .is_err(),
+            "the server isn't contacted for fetching"
+        );
+    }
+
+    #[test]
+    fn stalled_servers_fail_the_fetch_within_the_push_timeout() {
+        let suite = Suite::default();
+        let user = suite.sign_in();
+        let case = suite.new_case();
+        let unresponsive = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/code.git", unresponsive.local_addr().unwrap());
+        suite
+            .projects
+            .update(&UpdateRequest {
+                id: case.project.id,
+                api: Some(api_project(Some(url))),
+                ..Default::default()
+            })
+            .unwrap();
+        let case = case.refresh(&suite);
+        let mut settings = AppSettings::default();
+        settings.code_push.push_timeout_seconds = 1;
+        let ctx = CommandContext::open(&case.project, settings).unwrap();
+
+        let started = Instant::now();
+        let err = fetch_remote_branches(&ctx, &user).unwrap_err();
+        assert!(
+            matches!(
+                err.downcast_ref::<RemoteError>(),
+                Some(RemoteError::Network(_))
+            ),
+            "{err:?}"
+        );
+        assert!(started.elapsed() < Duration::from_secs(30));
+    }
+}
@@ -0,0 +1,293 @@
+use gitbutler_project::{CodeSyncMode, UpdateRequest};
+use gitbutler_sync::config::{apply_sync_config, SyncConfig, SyncSetting};
+use gitbutler_testsupport::Suite;
+
+use crate::api_project;
+
+fn link(suite: &Suite, project: &gitbutler_project::Project, sync: bool, sync_code: bool) {
+    let mut api = api_project(Some("https://gitbutler.com/code.git".into()));
+    api.sync = sync;
+    api.sync_code = sync_code;
+    suite
+        .projects
+        .update(&UpdateRequest {
+            id: project.id,
+            api: Some(api),
+            ..Default::default()
+        })
+        .unwrap();
+}
+
+#[test]
+fn unlinked_projects_sync_nothing() {
+    let suite = Suite::default();
+    let case = suite.new_case();
+    assert_eq!(
+        SyncConfig::from_project(&case.project),
+        SyncConfig {
+            oplog: false,
+            code: false,
+            code_sync_mode: CodeSyncMode::Full,
+            push_submodules: false,
+            ref_retention: Default::default(),
+            code_push_excluded_refs: Vec::new(),
+            code_push_batch_size: None,
+            code_push_min_interval_seconds: None,
+        }
+    );
+}
+
+#[test]
+fn diff_between_projects() {
+    let suite = Suite::default();
+    let template = suite.new_case();
+    let other = suite.new_case();
+    link(&suite, &template.project, true, true);
+    link(&suite, &other.project, true, false);
+
+    let template = SyncConfig::from_project(&suite.projects.get(template.project.id).unwrap());
+    let other = SyncConfig::from_project(&suite.projects.get(other.project.id).unwrap());
+    assert_eq!(template.diff(&other), vec![SyncSetting::Code]);
+    assert!(template.diff(&template).is_empty());
+}
+
+#[test]
+fn apply_copies_settings_and_reports_changes() {
+    let suite = Suite::default();
+    let template = suite.new_case();
+    let target = suite.new_case();
+    link(&suite, &template.project, true, true);
+    link(&suite, &target.project, false, false);
+
+    let config = SyncConfig::from_project(&suite.projects.get(template.project.id).unwrap());
+    let changed = apply_sync_config(&suite.projects, target.project.id, &config).unwrap();
+    assert_eq!(changed, vec![SyncSetting::Oplog, SyncSetting::Code]);
+    assert_eq!(
+        SyncConfig::from_project(&suite.projects.get(target.project.id).unwrap()),
+        config
+    );
+
+    let changed = apply_sync_config(&suite.projects, target.project.id, &config).unwrap();
+    assert!(
+        changed.is_empty(),
+        "applying the same config again changes nothing"
+    );
+}
+
+#[test]
+fn apply_sets_code_sync_mode() {
+    let suite = Suite::default();
+    let case = suite.new_case();
+    link(&suite, &case.project, true, true);
+
+    let mut config = SyncConfig::from_project(&suite.projects.get(case.project.id).unwrap());
+    config.code_sync_mode = CodeSyncMode::Minimal;
+    let changed = apply_sync_config(&suite.projects, case.project.id, &config).unwrap();
+    assert_eq!(changed, vec![SyncSetting::CodeSyncMode]);
+    assert_eq!(
+        suite.projects.get(case.project.id).unwrap().code_sync_mode,
+        CodeSyncMode::Minimal
+    );
+}
+
+#[test]
+fn apply_sets_push_submodules() {
+    let suite = Suite::default();
+    let case = suite.new_case();
+    link(&suite, &case.project, true, true);
+
+    let mut config = SyncConfig::from_project(&suite.projects.get(case.project.id).unwrap());
+    config.push_submodules = true;
+    let changed = apply_sync_config(&suite.projects, case.project.id, &config).unwrap();
+    assert_eq!(changed, vec![SyncSetting::PushSubmodules]);
+    assert!(suite.projects.get(case.project.id).unwrap().push_submodules);
+}
+
+#[test]
+fn apply_sets_excluded_refs() {
+    let suite = Suite::default();
+    let case = suite.new_case();
+    link(&suite, &case.project, true, true);
+
+    let mut config = SyncConfig::from_project(&suite.projects.get(case.project.id).unwrap());
+    config.code_push_excluded_refs = vec!["refs/gitbutler/tmp-*".into()];
+    let changed = apply_sync_config(&suite.projects, case.project.id, &config).unwrap();
+    assert_eq!(changed, vec![SyncSetting::CodePushExcludedRefs]);
+    assert_eq!(
+        suite
+            .projects
+            .get(case.project.id)
+            .unwrap()
+            .code_push_excluded_refs,
+        config.code_push_excluded_refs
+    );
+}
+
+#[test]
+fn apply_rejects_invalid_excluded_refs() {
+    let suite = Suite::default();
+    let case = suite.new_case();
+    link(&suite, &case.project, true, true);
+
+    let mut config = SyncConfig::from_project(&suite.projects.get(case.project.id).unwrap());
+    config.code_push_excluded_refs = vec!["refs/heads/[".into()];
+    let err = apply_sync_config(&suite.projects, case.project.id, &config).unwrap_err();
+    assert!(err
+        .chain()
+        .any(|cause| cause.to_string() == "invalid pattern of excluded refs: 'refs/heads/['"));
+    assert!(suite
+        .projects
+        .get(case.project.id)
+        .unwrap()
+        .code_push_excluded_refs
+        .is_empty());
+}
+
+#[test]
+fn apply_sets_and_unsets_batch_size() {
+    let suite = Suite::default();
+    let case = suite.new_case();
+    link(&suite, &case.project, true, true);
+
+    let mut config = SyncConfig::from_project(&suite.projects.get(case.project.id).unwrap());
+    config.code_push_batch_size = Some(50);
+    let changed = apply_sync_config(&suite.projects, case.project.id, &config).unwrap();
+    assert_eq!(changed, vec![SyncSetting::CodePushBatchSize]);
+    assert_eq!(
+        suite
+            .projects
+            .get(case.project.id)
+            .unwrap()
+            .code_push_batch_size,
+        Some(50)
+    );
+
+    config.code_push_batch_size = None;
+    let changed = apply_sync_config(&suite.projects, case.project.id, &config).unwrap();
+    assert_eq!(changed, vec![SyncSetting::CodePushBatchSize]);
+    assert_eq!(
+        suite
+            .projects
+            .get(case.project.id)
+            .unwrap()
+            .code_push_batch_size,
+        None,
+        "the default batch size is used again"
+    );
+}
+
+#[test]
+fn apply_sets_and_unsets_min_interval() {
+    let suite = Suite::default();
+    let case = suite.new_case();
+    link(&suite, &case.project, true, true);
+
+    let mut config = SyncConfig::from_project(&suite.projects.get(case.project.id).unwrap());
+    config.code_push_min_interval_seconds = Some(60);
+    let changed = apply_sync_config(&suite.projects, case.project.id, &config).unwrap();
+    assert_eq!(changed, vec![SyncSetting::CodePushMinInterval]);
+    assert_eq!(
+        suite
+            .projects
+            .get(case.project.id)
+            .unwrap()
+            .code_push_min_interval_seconds,
+        Some(60)
+    );
+
+    config.code_push_min_interval_seconds = None;
+    let changed = apply_sync_config(&suite.projects, case.project.id, &config).unwrap();
+    assert_eq!(changed, vec![SyncSetting::CodePushMinInterval]);
+    assert_eq!(
+        suite
+            .projects
+            .get(case.project.id)
+            .unwrap()
+            .code_push_min_interval_seconds,
+        None
+    );
+}
+
+#[test]
+fn apply_rejects_batch_sizes_out_of_range() {
+    let suite = Suite::default();
+    let case = suite.new_case();
+    link(&suite, &case.project, true, true);
+
+    let mut config = SyncConfig::from_project(&suite.projects.get(case.project.id).unwrap());
+    for size in [0, 10_001] {
+        config.code_push_batch_size = Some(size);
+        let err = apply_sync_config(&suite.projects, case.project.id, &config).unwrap_err();
+        assert!(err
+            .root_cause()
+            .to_string()
+            .ends_with("which must be between 1 and 10000"));
+    }
+    assert_eq!(
+        suite
+            .projects
+            .get(case.project.id)
+            .unwrap()
+            .code_push_batch_size,
+        None
+    );
+}
+
+#[test]
+fn apply_rejects_code_sync_without_code_url() {
+    let suite = Suite::default();
+    let case = suite.new_case();
+    suite
+        .projects
+        .update(&UpdateRequest {
+            id: case.project.id,
+            api: Some(api_project(None)),
+            ..Default::default()
+        })
+        .unwrap();
+
+    let err = apply_sync_config(
+        &suite.projects,
+        case.project.id,
+        &SyncConfig {
+            oplog: true,
+            code: true,
+            code_sync_mode: CodeSyncMode::Full,
+            push_submodules: false,
+            ref_retention: Default::default(),
+            code_push_excluded_refs: Vec::new(),
+            code_push_batch_size: None,
+            code_push_min_interval_seconds: None,
+        },
+    )
+    .unwrap_err();
+    assert!(err
+        .root_cause()
+        .to_string()
+        .ends_with("as it has no code URL"));
+}
+
+#[test]
+fn apply_rejects_sync_for_unlinked_projects() {
+    let suite = Suite::default();
+    let case = suite.new_case();
+    let err = apply_sync_config(
+        &suite.projects,
+        case.project.id,
+        &SyncConfig {
+            oplog: true,
+            code: false,
+            code_sync_mode: CodeSyncMode::Full,
+            push_submodules: false,
+            ref_retention: Default::default(),
+            code_push_excluded_refs: Vec::new(),
+            code_push_batch_size: None,
+            code_push_min_interval_seconds: None,
+        },
+    )
+    .unwrap_err();
+    assert!(err
+        .root_cause()
+        .to_string()
+        .ends_with("as it isn't linked to GitButler"));
+}
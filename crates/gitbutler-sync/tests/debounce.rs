@@ -0,0 +1,71 @@
+use std::time::{Duration, Instant};
+
+use gitbutler_sync::debounce::PushDebounce;
+
+const WINDOW: Duration = Duration::from_millis(500);
+const MAX_DELAY: Duration = Duration::from_secs(10);
+
+#[test]
+fn bursts_of_requests_cause_a_single_push() {
+    let mut debounce = PushDebounce::new(WINDOW, MAX_DELAY);
+    let start = Instant::now();
+    for n in 0..10 {
+        let now = start + Duration::from_millis(n * 40);
+        debounce.request("project", now);
+        assert!(
+            debounce.take_due(now).is_empty(),
+            "held back within the window"
+        );
+    }
+    let last = start + Duration::from_millis(9 * 40);
+    assert_eq!(debounce.next_due(), Some(last + WINDOW));
+    assert!(debounce
+        .take_due(last + WINDOW - Duration::from_millis(1))
+        .is_empty());
+    assert_eq!(
+        debounce.take_due(last + WINDOW),
+        ["project"],
+        "pushed once the requests stop"
+    );
+    assert!(
+        debounce.take_due(last + WINDOW * 10).is_empty(),
+        "all requests were handled by the single push"
+    );
+    assert_eq!(debounce.next_due(), None);
+}
+
+#[test]
+fn projects_that_keep_changing_are_pushed_regularly() {
+    let mut debounce = PushDebounce::new(WINDOW, MAX_DELAY);
+    let start = Instant::now();
+    let mut pushes = Vec::new();
+    for n in 0..=110 {
+        let now = start + Duration::from_millis(n * 200);
+        debounce.request("project", now);
+        pushes.extend(debounce.take_due(now).into_iter().map(|_| now - start));
+    }
+    assert_eq!(
+        pushes,
+        [MAX_DELAY, MAX_DELAY * 2 + Duration::from_millis(200)],
+        "constant changes never leave the window, but are pushed after the longest delay"
+    );
+}
+
+#[test]
+fn projects_are_held_back_independently() {
+    let mut debounce = PushDebounce::new(WINDOW, MAX_DELAY);
+    let start = Instant::now();
+    debounce.request("a", start);
+    debounce.request("b", start + WINDOW / 2);
+    assert_eq!(debounce.next_due(), Some(start + WINDOW));
+    assert_eq!(debounce.take_due(start + WINDOW), ["a"]);
+    assert_eq!(debounce.take_due(start + WINDOW + WINDOW / 2), ["b"]);
+}
+
+#[test]
+fn without_window_requests_are_due_right_away() {
+    let mut debounce = PushDebounce::new(Duration::ZERO, MAX_DELAY);
+    let now = Instant::now();
+    debounce.request("project", now);
+    assert_eq!(debounce.take_due(now), ["project"]);
+}
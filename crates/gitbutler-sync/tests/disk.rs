@@ -0,0 +1,83 @@
+use std::path::Path;
+
+use but_settings::app_settings::CodePushSettings;
+use gitbutler_sync::{
+    disk::{check_first_push, estimate_first_push_bytes, DiskSpace},
+    events::Event,
+};
+use gitbutler_testsupport::test_repository;
+
+struct FakeDisk {
+    available: u64,
+}
+
+impl DiskSpace for FakeDisk {
+    fn available_bytes(&self, _path: &Path) -> anyhow::Result<u64> {
+        Ok(self.available)
+    }
+}
+
+struct UnreachableDisk;
+
+impl DiskSpace for UnreachableDisk {
+    fn available_bytes(&self, _path: &Path) -> anyhow::Result<u64> {
+        unreachable!("small pushes don't check the disk")
+    }
+}
+
+const SETTINGS: CodePushSettings = CodePushSettings {
+    disk_check_threshold_bytes: 0,
+    disk_safety_margin_bytes: 1000,
+    max_batches_per_tick: 0,
+    max_refs_per_tick: 0,
+    max_seconds_per_tick: 0,
+    max_memory_bytes: 0,
+    network_retries: 0,
+    push_timeout_seconds: 0,
+    verify_target_push: false,
+    push_debounce_millis: 0,
+    push_debounce_max_seconds: 0,
+};
+
+#[test]
+fn low_disk_skips_first_push() {
+    let (repo, _tmp) = test_repository();
+    let needed = estimate_first_push_bytes(&repo).unwrap() + 1000;
+
+    let event = check_first_push(
+        &repo,
+        &SETTINGS,
+        &FakeDisk {
+            available: needed - 1,
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        event,
+        Some(Event::SkippedLowDisk {
+            needed,
+            available: needed - 1
+        })
+    );
+}
+
+#[test]
+fn enough_disk_allows_first_push() {
+    let (repo, _tmp) = test_repository();
+    let needed = estimate_first_push_bytes(&repo).unwrap() + 1000;
+
+    let event = check_first_push(&repo, &SETTINGS, &FakeDisk { available: needed }).unwrap();
+    assert_eq!(event, None);
+}
+
+#[test]
+fn small_pushes_are_not_checked() {
+    let (repo, _tmp) = test_repository();
+    let settings = CodePushSettings {
+        disk_check_threshold_bytes: u64::MAX,
+        ..SETTINGS
+    };
+
+    let event = check_first_push(&repo, &settings, &UnreachableDisk).unwrap();
+    assert_eq!(event, None);
+}
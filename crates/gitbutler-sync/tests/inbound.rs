@@ -0,0 +1,143 @@
+use gitbutler_project::UpdateRequest;
+use gitbutler_sync::{
+    budget::PushBudget,
+    cloud::{fetch_remote_branches, push_repo, RemoteError},
+    inbound::{inbound_commits, InboundRef},
+};
+use gitbutler_testsupport::{empty_bare_repository, Case, Suite};
+use tokio_util::sync::CancellationToken;
+
+use crate::{api_project, link_to};
+
+/// Set up a project whose code was pushed to `server`.
+fn pushed(suite: &Suite, server: &git2::Repository) -> Case {
+    let user = suite.sign_in();
+    let case = suite.new_case();
+    gitbutler_testsupport::virtual_branches::set_test_target(&case.ctx).unwrap();
+    let case = link_to(suite, case, server);
+    push_repo(
+        &case.ctx,
+        &user,
+        &suite.projects,
+        &PushBudget::default(),
+        &CancellationToken::new(),
+        &|_event| {},
+    )
+    .unwrap();
+    case.refresh(suite)
+}
+
+/// Create a commit on top of `refname` in `repo`, and return it.
+fn commit_on(repo: &git2::Repository, refname: &str) -> git2::Oid {
+    let parent = repo
+        .find_reference(refname)
+        .unwrap()
+        .peel_to_commit()
+        .unwrap();
+    let signature = git2::Signature::now("other", "other@example.com").unwrap();
+    repo.commit(
+        Some(refname),
+        &signature,
+        &signature,
+        "pushed from elsewhere",
+        &parent.tree().unwrap(),
+        &[&parent],
+    )
+    .unwrap()
+}
+
+#[test]
+fn commits_only_the_server_has_are_listed_per_branch() {
+    let suite = Suite::default();
+    let user = suite.sign_in();
+    let (server, _server_tmp) = empty_bare_repository();
+    let case = pushed(&suite, &server);
+    let local_master = case.ctx.repo().refname_to_id("refs/heads/master").unwrap();
+    let first = commit_on(&server, "refs/heads/master");
+    let second = commit_on(&server, "refs/heads/master");
+    let master = server.refname_to_id("refs/heads/master").unwrap();
+    server
+        .reference("refs/heads/feature", master, false, "")
+        .unwrap();
+    let feature = commit_on(&server, "refs/heads/feature");
+
+    let inbound = inbound_commits(&case.ctx, &user).unwrap();
+    assert_eq!(
+        inbound,
+        vec![
+            InboundRef {
+                name: "refs/heads/feature".into(),
+                commits: vec![feature, second, first],
+            },
+            InboundRef {
+                name: "refs/heads/master".into(),
+                commits: vec![second, first],
+            },
+        ]
+    );
+    assert_eq!(
+        case.ctx.repo().refname_to_id("refs/heads/master").unwrap(),
+        local_master,
+        "no local ref is changed"
+    );
+}
+
+#[test]
+fn branches_fetched_before_dont_hide_commits() {
+    let suite = Suite::default();
+    let user = suite.sign_in();
+    let (server, _server_tmp) = empty_bare_repository();
+    let case = pushed(&suite, &server);
+    let commit = commit_on(&server, "refs/heads/master");
+    fetch_remote_branches(&case.ctx, &user).unwrap();
+    assert_eq!(
+        case.ctx
+            .repo()
+            .refname_to_id("refs/remotes/gitbutler/master")
+            .unwrap(),
+        commit
+    );
+
+    assert_eq!(
+        inbound_commits(&case.ctx, &user).unwrap(),
+        vec![InboundRef {
+            name: "refs/heads/master".into(),
+            commits: vec![commit],
+        }]
+    );
+}
+
+#[test]
+fn nothing_is_listed_when_in_sync() {
+    let suite = Suite::default();
+    let user = suite.sign_in();
+    let (server, _server_tmp) = empty_bare_repository();
+    let case = pushed(&suite, &server);
+
+    assert_eq!(inbound_commits(&case.ctx, &user).unwrap(), vec![]);
+}
+
+#[test]
+fn an_unreachable_server_is_a_network_error() {
+    let suite = Suite::default();
+    let user = suite.sign_in();
+    let case = suite.new_case();
+    suite
+        .projects
+        .update(&UpdateRequest {
+            id: case.project.id,
+            api: Some(api_project(Some("http://127.0.0.1:1/code.git".into()))),
+            ..Default::default()
+        })
+        .unwrap();
+    let case = case.refresh(&suite);
+
+    let err = inbound_commits(&case.ctx, &user).unwrap_err();
+    assert!(
+        matches!(
+            err.downcast_ref::<RemoteError>(),
+            Some(RemoteError::Network(_))
+        ),
+        "{err:?}"
+    );
+}
@@ -0,0 +1,54 @@
+use gitbutler_sync::{
+    events::Event,
+    memory::{AdaptiveBatchSize, MemoryUsage, ProcessMemory},
+};
+
+#[test]
+fn batches_shrink_when_memory_approaches_the_ceiling() {
+    let mut batch_size = AdaptiveBatchSize::new(12, 1000);
+    assert_eq!(
+        batch_size.observe(700),
+        Event::MemoryUsage {
+            current_bytes: 700,
+            peak_bytes: 700
+        }
+    );
+    assert_eq!(batch_size.size(), 12, "well below the ceiling");
+
+    assert_eq!(
+        batch_size.observe(800),
+        Event::MemoryUsage {
+            current_bytes: 800,
+            peak_bytes: 800
+        }
+    );
+    assert_eq!(batch_size.size(), 6);
+
+    assert_eq!(
+        batch_size.observe(400),
+        Event::MemoryUsage {
+            current_bytes: 400,
+            peak_bytes: 800
+        },
+        "the peak is retained"
+    );
+    assert_eq!(batch_size.size(), 6, "batches don't grow back");
+
+    for _ in 0..5 {
+        batch_size.observe(2000);
+    }
+    assert_eq!(batch_size.size(), 1, "batches never become empty");
+    assert_eq!(batch_size.peak_bytes(), 2000);
+}
+
+#[test]
+fn without_ceiling_the_batch_size_is_fixed() {
+    let mut batch_size = AdaptiveBatchSize::new(12, 0);
+    batch_size.observe(u64::MAX);
+    assert_eq!(batch_size.size(), 12);
+}
+
+#[test]
+fn process_memory_is_measured() {
+    assert!(ProcessMemory.used_bytes().unwrap() > 0);
+}
@@ -0,0 +1,61 @@
+mod cas;
+mod cloud;
+mod config;
+mod debounce;
+mod disk;
+mod inbound;
+mod memory;
+mod plan;
+mod queue;
+mod quota;
+mod reconcile;
+
+use gitbutler_project::{ApiProject, UpdateRequest};
+use gitbutler_testsupport::{Case, Suite};
+
+/// An API project as it would be provided by GitButler, with code pushes going to `code_git_url`.
+pub fn api_project(code_git_url: Option<String>) -> ApiProject {
+    ApiProject {
+        name: "test".into(),
+        description: None,
+        repository_id: "repository-id".into(),
+        git_url: "https://gitbutler.com/oplog.git".into(),
+        code_git_url,
+        created_at: String::new(),
+        updated_at: String::new(),
+        sync: false,
+        sync_code: false,
+        reviews: false,
+    }
+}
+
+/// Link the project of `case` to GitButler with `server` as code remote.
+pub fn link_to(suite: &Suite, case: Case, server: &git2::Repository) -> Case {
+    let url = server.path().to_str().unwrap().to_owned();
+    suite
+        .projects
+        .update(&UpdateRequest {
+            id: case.project.id,
+            api: Some(api_project(Some(url))),
+            ..Default::default()
+        })
+        .unwrap();
+    case.refresh(suite)
+}
+
+/// Add `count` commits on top of `HEAD` of `repo`, each with the tree of its parent.
+pub fn linear_history(repo: &git2::Repository, count: usize) {
+    let signature = git2::Signature::now("test", "test@example.com").unwrap();
+    for n in 0..count {
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            &format!("commit {n}"),
+            &head.tree().unwrap(),
+            &[&head],
+        )
+        .unwrap();
+    }
+}
@@ -0,0 +1,289 @@
+use std::cell::RefCell;
+
+use gitbutler_sync::{events::Event, plan, transport::GitTransport};
+use gitbutler_testsupport::{empty_bare_repository, Suite};
+
+use crate::{linear_history, link_to};
+
+#[test]
+fn planning_describes_the_push_without_pushing() {
+    let suite = Suite::default();
+    let (server, _server_tmp) = empty_bare_repository();
+    let case = suite.new_case();
+    gitbutler_testsupport::virtual_branches::set_test_target(&case.ctx).unwrap();
+    let case = link_to(&suite, case, &server);
+
+    let plan = plan::create(&case.ctx).unwrap();
+    let master = case.ctx.repo().refname_to_id("refs/heads/master").unwrap();
+    assert_eq!(plan.project_id, case.project.id);
+    assert_eq!(plan.target, master);
+    assert_eq!(plan.last_pushed, None);
+    assert_eq!(
+        plan.batches.last(),
+        Some(&master),
+        "the target is pushed last"
+    );
+    assert!(plan.refs.iter().any(|r| r.name == "refs/heads/master"));
+    assert!(plan.estimated_bytes > 0);
+    assert_eq!(server.references().unwrap().count(), 0);
+}
+
+#[test]
+fn dry_runs_report_the_ref_specs_without_pushing() {
+    let suite = Suite::default();
+    let (server, _server_tmp) = empty_bare_repository();
+    let case = suite.new_case();
+    gitbutler_testsupport::virtual_branches::set_test_target(&case.ctx).unwrap();
+    let case = link_to(&suite, case, &server);
+
+    let events = RefCell::new(Vec::new());
+    let pushes = plan::dry_run(&case.ctx, &|event| events.borrow_mut().push(event)).unwrap();
+    let master = case.ctx.repo().refname_to_id("refs/heads/master").unwrap();
+    let target_push = pushes.len() - 2;
+    assert!(
+        pushes[target_push].contains(&format!("+{}:refs/{}", master, case.project.id)),
+        "the last batch moves the target"
+    );
+    assert!(pushes
+        .last()
+        .unwrap()
+        .contains(&format!("+{master}:refs/heads/master")));
+    assert_eq!(
+        events.into_inner(),
+        pushes
+            .iter()
+            .map(|ref_specs| Event::WouldPush {
+                ref_specs: ref_specs.clone()
+            })
+            .collect::<Vec<_>>()
+    );
+
+    assert_eq!(server.references().unwrap().count(), 0);
+    let case = case.refresh(&suite);
+    assert!(
+        case.project.gitbutler_code_push_state.is_none(),
+        "the project is unchanged"
+    );
+}
+
+#[test]
+fn dry_runs_only_report_the_refs_a_push_would_send() {
+    let suite = Suite::default();
+    let user = suite.sign_in();
+    let (server, _server_tmp) = empty_bare_repository();
+    let case = suite.new_case();
+    {
+        let repo = case.ctx.repo();
+        let head = repo.head().unwrap().target().unwrap();
+        repo.reference("refs/heads/feature", head, false, "")
+            .unwrap();
+    }
+    gitbutler_testsupport::virtual_branches::set_test_target(&case.ctx).unwrap();
+    let case = link_to(&suite, case, &server);
+    let plan = plan::create(&case.ctx).unwrap();
+    plan::execute(
+        &case.ctx,
+        &user,
+        &suite.projects,
+        &GitTransport,
+        &plan,
+        &|_event| {},
+    )
+    .unwrap();
+    case.ctx
+        .repo()
+        .find_reference("refs/heads/feature")
+        .unwrap()
+        .delete()
+        .unwrap();
+
+    let case = case.refresh(&suite);
+    let pushes = plan::dry_run(&case.ctx, &|_event| {}).unwrap();
+    assert_eq!(
+        pushes[1..],
+        [vec![":refs/heads/feature".to_owned()]],
+        "refs that didn't move are left out, but the deleted one is removed"
+    );
+}
+
+#[test]
+fn executing_a_plan_pushes_it() {
+    let suite = Suite::default();
+    let user = suite.sign_in();
+    let (server, _server_tmp) = empty_bare_repository();
+    let case = suite.new_case();
+    gitbutler_testsupport::virtual_branches::set_test_target(&case.ctx).unwrap();
+    let case = link_to(&suite, case, &server);
+
+    let plan = plan::create(&case.ctx).unwrap();
+    plan::execute(
+        &case.ctx,
+        &user,
+        &suite.projects,
+        &GitTransport,
+        &plan,
+        &|_event| {},
+    )
+    .unwrap();
+    assert_eq!(
+        server
+            .find_reference(&format!("refs/{}", case.project.id))
+            .unwrap()
+            .target(),
+        Some(plan.target)
+    );
+    assert!(server.find_reference("refs/heads/master").is_ok());
+
+    let case = case.refresh(&suite);
+    let plan = plan::create(&case.ctx).unwrap();
+    assert_eq!(plan.last_pushed, Some(plan.target));
+    assert_eq!(plan.commits, 0, "the server has the whole history");
+    assert_eq!(
+        plan.batches,
+        [plan.target],
+        "the target is pushed again, which is cheap"
+    );
+    assert!(
+        plan.refs.is_empty(),
+        "refs that didn't move aren't pushed again"
+    );
+}
+
+#[test]
+fn outdated_plans_are_rejected() {
+    let suite = Suite::default();
+    let user = suite.sign_in();
+    let (server, _server_tmp) = empty_bare_repository();
+    let case = suite.new_case();
+    gitbutler_testsupport::virtual_branches::set_test_target(&case.ctx).unwrap();
+    let case = link_to(&suite, case, &server);
+
+    let plan = plan::create(&case.ctx).unwrap();
+    {
+        let repo = case.ctx.repo();
+        let head = repo.head().unwrap().target().unwrap();
+        repo.reference("refs/heads/new", head, false, "").unwrap();
+    }
+
+    let err = plan::execute(
+        &case.ctx,
+        &user,
+        &suite.projects,
+        &GitTransport,
+        &plan,
+        &|_event| {},
+    )
+    .unwrap_err();
+    assert!(err.root_cause().to_string().contains("outdated"));
+    assert_eq!(server.references().unwrap().count(), 0);
+}
+
+mod resume {
+    use std::{cell::RefCell, panic::AssertUnwindSafe};
+
+    use gitbutler_sync::{
+        events::{Event, PushPhase},
+        plan::ResumeOutcome,
+    };
+    use gitbutler_testsupport::Case;
+
+    use super::*;
+
+    /// Set up a project whose target history needs multiple batches, and whose plan execution
+    /// was interrupted after pushing the first batch.
+    fn interrupted_plan(suite: &Suite, server: &git2::Repository) -> Case {
+        let user = suite.sign_in();
+        let case = suite.new_case();
+        linear_history(case.ctx.repo(), 20);
+        gitbutler_testsupport::virtual_branches::set_test_target(&case.ctx).unwrap();
+        let case = link_to(suite, case, server);
+
+        let plan = plan::create(&case.ctx).unwrap();
+        assert_eq!(plan.batches.len(), 2);
+        let crash = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            plan::execute(
+                &case.ctx,
+                &user,
+                &suite.projects,
+                &GitTransport,
+                &plan,
+                &|event| {
+                    if event
+                        == Event::Phase(PushPhase::PushingHistory {
+                            batch: 2,
+                            batches: 2,
+                        })
+                    {
+                        panic!("the app quits");
+                    }
+                },
+            )
+        }));
+        assert!(crash.is_err());
+
+        let case = case.refresh(suite);
+        assert_eq!(plan::saved(&case.project).unwrap(), Some(plan));
+        case
+    }
+
+    #[test]
+    fn continues_with_a_valid_plan() {
+        let suite = Suite::default();
+        let user = suite.sign_in();
+        let (server, _server_tmp) = empty_bare_repository();
+        let case = interrupted_plan(&suite, &server);
+
+        let events = RefCell::new(Vec::new());
+        let outcome = plan::resume(&case.ctx, &user, &suite.projects, &GitTransport, &|event| {
+            events.borrow_mut().push(event)
+        })
+        .unwrap();
+        assert_eq!(outcome, ResumeOutcome::Executed);
+        assert!(
+            events
+                .into_inner()
+                .iter()
+                .any(|event| matches!(event, Event::PushStarted { batches: 1, .. })),
+            "the first batch isn't pushed again"
+        );
+        assert!(server
+            .find_reference(&format!("refs/{}", case.project.id))
+            .is_ok());
+        assert!(server.find_reference("refs/heads/master").is_ok());
+        assert_eq!(plan::saved(&case.project).unwrap(), None);
+    }
+
+    #[test]
+    fn plans_again_if_the_saved_plan_is_outdated() {
+        let suite = Suite::default();
+        let user = suite.sign_in();
+        let (server, _server_tmp) = empty_bare_repository();
+        let case = interrupted_plan(&suite, &server);
+        {
+            let repo = case.ctx.repo();
+            let head = repo.head().unwrap().target().unwrap();
+            repo.reference("refs/heads/new", head, false, "").unwrap();
+        }
+
+        let outcome = plan::resume(
+            &case.ctx,
+            &user,
+            &suite.projects,
+            &GitTransport,
+            &|_event| {},
+        )
+        .unwrap();
+        let ResumeOutcome::Outdated(plan) = outcome else {
+            panic!("expected a new plan, got {outcome:?}");
+        };
+        assert!(plan.refs.iter().any(|r| r.name == "refs/heads/new"));
+        assert_eq!(plan.batches.len(), 1, "only the second batch is left");
+        assert!(
+            server
+                .find_reference(&format!("refs/{}", case.project.id))
+                .is_err(),
+            "nothing is pushed without approval"
+        );
+        assert_eq!(plan::saved(&case.project).unwrap(), None);
+    }
+}
@@ -0,0 +1,145 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Barrier,
+};
+
+use anyhow::anyhow;
+use gitbutler_sync::queue::PushQueue;
+
+#[test]
+fn pushes_requested_while_one_runs_are_run_after_it() {
+    let queue = PushQueue::default();
+    let pushes = AtomicUsize::new(0);
+    let started = Barrier::new(2);
+    let released = Barrier::new(2);
+    std::thread::scope(|scope| {
+        let first = scope.spawn(|| {
+            queue.run(|| {
+                if pushes.fetch_add(1, Ordering::SeqCst) == 0 {
+                    started.wait();
+                    released.wait();
+                }
+                Ok(())
+            })
+        });
+        started.wait();
+        for _ in 0..2 {
+            queue
+                .run(|| unreachable!("the running push runs the requested ones"))
+                .unwrap();
+        }
+        released.wait();
+        first.join().unwrap().unwrap();
+    });
+    assert_eq!(
+        pushes.load(Ordering::SeqCst),
+        2,
+        "the requests made during the first push are coalesced into a single one"
+    );
+
+    queue
+        .run(|| {
+            pushes.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(
+        pushes.load(Ordering::SeqCst),
+        3,
+        "the queue runs pushes right away once it's idle"
+    );
+}
+
+#[test]
+fn the_last_push_decides_the_result() {
+    let queue = PushQueue::default();
+    let pushes = AtomicUsize::new(0);
+    let res = queue.run(|| {
+        if pushes.fetch_add(1, Ordering::SeqCst) == 0 {
+            queue.run(|| unreachable!("the running push runs the requested ones"))?;
+            return Err(anyhow!("superseded"));
+        }
+        Err(anyhow!("last"))
+    });
+    assert_eq!(pushes.load(Ordering::SeqCst), 2);
+    assert_eq!(res.unwrap_err().to_string(), "last");
+
+    queue.run(|| Ok(())).unwrap();
+}
+
+#[test]
+fn pushes_run_now_wait_for_the_running_push() {
+    let queue = PushQueue::default();
+    let pushes = AtomicUsize::new(0);
+    let started = Barrier::new(2);
+    let released = Barrier::new(2);
+    std::thread::scope(|scope| {
+        let pending = scope.spawn(|| {
+            queue.run(|| {
+                pushes.fetch_add(1, Ordering::SeqCst);
+                started.wait();
+                released.wait();
+                Ok(())
+            })
+        });
+        started.wait();
+        let now = scope.spawn(|| queue.run_now(|| Ok(pushes.fetch_add(1, Ordering::SeqCst))));
+        released.wait();
+        assert_eq!(
+            now.join().unwrap().unwrap(),
+            1,
+            "the push runs after the running one, and its result is returned"
+        );
+        pending.join().unwrap().unwrap();
+    });
+    assert_eq!(pushes.load(Ordering::SeqCst), 2);
+
+    assert_eq!(queue.run_now(|| Ok("idle")).unwrap(), "idle");
+}
+
+#[test]
+fn exclusive_work_runs_once_before_the_pushes_requested_meanwhile() {
+    let queue = PushQueue::default();
+    let work = AtomicUsize::new(0);
+    let pushes = AtomicUsize::new(0);
+    let res = queue.run_exclusive(
+        || {
+            work.fetch_add(1, Ordering::SeqCst);
+            for _ in 0..2 {
+                queue.run(|| unreachable!("pushes wait for the exclusive work"))?;
+            }
+            Ok("resynced")
+        },
+        || {
+            pushes.fetch_add(1, Ordering::SeqCst);
+            Err(anyhow!("only logged"))
+        },
+    );
+    assert_eq!(res.unwrap(), "resynced");
+    assert_eq!(work.load(Ordering::SeqCst), 1, "the work isn't repeated");
+    assert_eq!(
+        pushes.load(Ordering::SeqCst),
+        1,
+        "the requested pushes run once the work is done, coalesced into one"
+    );
+
+    queue.run(|| Ok(())).unwrap();
+}
+
+#[test]
+fn panicking_pushes_leave_the_queue_idle() {
+    let queue = PushQueue::default();
+    let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        queue.run(|| panic!("push panicked")).ok();
+    }));
+    assert!(res.is_err());
+
+    let pushes = AtomicUsize::new(0);
+    queue
+        .run(|| {
+            pushes.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(pushes.load(Ordering::SeqCst), 1, "later pushes still run");
+}
@@ -0,0 +1,40 @@
+use gitbutler_sync::quota::{self, StorageQuota};
+use gitbutler_testsupport::Suite;
+
+#[test]
+fn quota_is_parsed_from_progress() {
+    let progress = "Resolving deltas: 100% (3/3)\r\ngitbutler-quota used=1024 total=4096\n";
+    assert_eq!(
+        quota::parse(progress),
+        Some(StorageQuota {
+            used_bytes: 1024,
+            total_bytes: 4096
+        })
+    );
+}
+
+#[test]
+fn last_reported_quota_wins() {
+    let progress = "gitbutler-quota used=1 total=10\ngitbutler-quota total=10 used=2 plan=free\n";
+    assert_eq!(
+        quota::parse(progress),
+        Some(StorageQuota {
+            used_bytes: 2,
+            total_bytes: 10
+        })
+    );
+}
+
+#[test]
+fn incomplete_quotas_are_ignored() {
+    assert_eq!(quota::parse("gitbutler-quota used=1024\n"), None);
+    assert_eq!(quota::parse("gitbutler-quota used=lots total=10\n"), None);
+    assert_eq!(quota::parse("Counting objects: 3, done.\n"), None);
+}
+
+#[test]
+fn projects_without_reported_quota_have_none() {
+    let suite = Suite::default();
+    let case = suite.new_case();
+    assert_eq!(quota::last(&case.project).unwrap(), None);
+}
@@ -0,0 +1,154 @@
+use gitbutler_project::{CodePushState, UpdateRequest};
+use gitbutler_sync::{
+    budget::PushBudget,
+    cloud::push_repo,
+    reconcile::{reconcile, Reconciliation},
+};
+use gitbutler_testsupport::{empty_bare_repository, Case, Suite};
+use tokio_util::sync::CancellationToken;
+
+use crate::{linear_history, link_to};
+
+/// Set up a project with a target history of multiple commits, pushed completely to `server`.
+fn pushed(suite: &Suite, server: &git2::Repository) -> Case {
+    let user = suite.sign_in();
+    let case = suite.new_case();
+    linear_history(case.ctx.repo(), 2);
+    gitbutler_testsupport::virtual_branches::set_test_target(&case.ctx).unwrap();
+    let case = link_to(suite, case, server);
+    push_repo(
+        &case.ctx,
+        &user,
+        &suite.projects,
+        &PushBudget::default(),
+        &CancellationToken::new(),
+        &|_event| {},
+    )
+    .unwrap();
+    case.refresh(suite)
+}
+
+fn master(case: &Case) -> git2::Oid {
+    case.ctx.repo().refname_to_id("refs/heads/master").unwrap()
+}
+
+fn parent_of(repo: &git2::Repository, id: git2::Oid) -> git2::Oid {
+    repo.find_commit(id).unwrap().parent_id(0).unwrap()
+}
+
+fn recorded(suite: &Suite, case: &Case) -> Option<git2::Oid> {
+    suite
+        .projects
+        .get(case.project.id)
+        .unwrap()
+        .gitbutler_code_push_state
+        .map(|state| state.id)
+}
+
+#[test]
+fn nothing_changes_when_in_sync() {
+    let suite = Suite::default();
+    let user = suite.sign_in();
+    let (server, _server_tmp) = empty_bare_repository();
+    let case = pushed(&suite, &server);
+
+    let reconciliation = reconcile(&case.ctx, &user, &suite.projects).unwrap();
+    assert_eq!(reconciliation, Reconciliation::InSync);
+    assert_eq!(recorded(&suite, &case), Some(master(&case)));
+}
+
+#[test]
+fn a_server_ahead_of_the_state_is_recorded() {
+    let suite = Suite::default();
+    let user = suite.sign_in();
+    let (server, _server_tmp) = empty_bare_repository();
+    let case = pushed(&suite, &server);
+    let master = master(&case);
+    let state = case.project.gitbutler_code_push_state.clone().unwrap();
+    suite
+        .projects
+        .update(&UpdateRequest {
+            id: case.project.id,
+            gitbutler_code_push_state: Some(CodePushState {
+                id: parent_of(case.ctx.repo(), master),
+                ..state
+            }),
+            ..Default::default()
+        })
+        .unwrap();
+    let case = case.refresh(&suite);
+
+    let reconciliation = reconcile(&case.ctx, &user, &suite.projects).unwrap();
+    assert_eq!(reconciliation, Reconciliation::Ahead { server: master });
+    assert_eq!(recorded(&suite, &case), Some(master));
+}
+
+#[test]
+fn a_server_behind_the_state_is_recorded() {
+    let suite = Suite::default();
+    let user = suite.sign_in();
+    let (server, _server_tmp) = empty_bare_repository();
+    let case = pushed(&suite, &server);
+    let parent = parent_of(case.ctx.repo(), master(&case));
+    server
+        .reference(&format!("refs/{}", case.project.id), parent, true, "")
+        .unwrap();
+
+    let reconciliation = reconcile(&case.ctx, &user, &suite.projects).unwrap();
+    assert_eq!(reconciliation, Reconciliation::Behind { server: parent });
+    assert_eq!(
+        recorded(&suite, &case),
+        Some(parent),
+        "the last commit is pushed again"
+    );
+}
+
+#[test]
+fn a_diverged_server_is_recorded() {
+    let suite = Suite::default();
+    let user = suite.sign_in();
+    let (server, _server_tmp) = empty_bare_repository();
+    let case = pushed(&suite, &server);
+    let parent = server
+        .find_commit(parent_of(case.ctx.repo(), master(&case)))
+        .unwrap();
+    let signature = git2::Signature::now("other", "other@example.com").unwrap();
+    let elsewhere = server
+        .commit(
+            Some(&format!("refs/{}", case.project.id)),
+            &signature,
+            &signature,
+            "pushed from elsewhere",
+            &parent.tree().unwrap(),
+            &[&parent],
+        )
+        .unwrap();
+
+    let reconciliation = reconcile(&case.ctx, &user, &suite.projects).unwrap();
+    assert_eq!(
+        reconciliation,
+        Reconciliation::Diverged { server: elsewhere }
+    );
+    assert_eq!(recorded(&suite, &case), Some(elsewhere));
+    assert!(
+        case.ctx.repo().find_commit(elsewhere).is_ok(),
+        "the commit of the server was fetched"
+    );
+}
+
+#[test]
+fn a_server_without_the_project_starts_over() {
+    let suite = Suite::default();
+    let user = suite.sign_in();
+    let (server, _server_tmp) = empty_bare_repository();
+    let case = pushed(&suite, &server);
+    server
+        .find_reference(&format!("refs/{}", case.project.id))
+        .unwrap()
+        .delete()
+        .unwrap();
+
+    let reconciliation = reconcile(&case.ctx, &user, &suite.projects).unwrap();
+    assert_eq!(reconciliation, Reconciliation::Missing);
+    assert_eq!(recorded(&suite, &case), None);
+}
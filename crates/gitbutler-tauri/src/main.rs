@@ -180,6 +180,9 @@ fn main() {
                     users::commands::set_user,
                     users::commands::delete_user,
                     users::commands::get_user,
+                    users::commands::list_accounts,
+                    users::commands::add_account,
+                    users::commands::delete_account,
                     projects::commands::add_project,
                     projects::commands::get_project,
                     projects::commands::update_project,
@@ -240,6 +243,7 @@ fn main() {
                     stack::update_series_pr_number,
                     stack::push_stack,
                     stack::push_stack_to_review,
+                    stack::push_stack_to_gitbutler,
                     secret::secret_get_global,
                     secret::secret_set_global,
                     undo::list_snapshots,
@@ -255,6 +259,18 @@ fn main() {
                     askpass::commands::submit_prompt_response,
                     remotes::list_remotes,
                     remotes::add_remote,
+                    remotes::fetch_gitbutler_branches,
+                    remotes::inbound_gitbutler_commits,
+                    remotes::plan_code_push,
+                    remotes::dry_run_code_push,
+                    remotes::force_code_resync,
+                    remotes::code_push_commits_left,
+                    remotes::code_push_refs,
+                    remotes::code_push_sync_status,
+                    remotes::execute_code_push,
+                    remotes::saved_code_push_plan,
+                    remotes::resume_code_push,
+                    remotes::reconcile_code_push,
                     modes::operating_mode,
                     modes::enter_edit_mode,
                     modes::save_edit_and_return_to_workspace,
@@ -267,6 +283,7 @@ fn main() {
                     settings::update_onboarding_complete,
                     settings::update_telemetry,
                     settings::update_feature_flags,
+                    settings::update_code_push,
                     workspace::stacks,
                     workspace::stack_branches,
                     workspace::stack_branch_local_and_remote_commits,
@@ -1,7 +1,18 @@
-use crate::error::Error;
+use crate::{error::Error, WindowState};
+use anyhow::{anyhow, Context};
+use but_settings::AppSettingsWithDiskSync;
+use gitbutler_command_context::CommandContext;
+use gitbutler_error::error::Code;
 use gitbutler_project as projects;
 use gitbutler_project::ProjectId;
 use gitbutler_repo::{GitRemote, RepoCommands};
+use gitbutler_sync::{
+    cloud::{PushRefs, ServerBranch, SyncStatus},
+    inbound::InboundRef,
+    plan::{ResumeOutcome, SyncPlan},
+    reconcile::Reconciliation,
+};
+use gitbutler_user::{self as users, User};
 use tauri::State;
 use tracing::instrument;
 
@@ -26,3 +37,191 @@ pub fn add_remote(
     let project = projects.get(project_id)?;
     Ok(project.add_remote(name, url)?)
 }
+
+#[tauri::command(async)]
+#[instrument(skip(projects, users, settings), err(Debug))]
+pub fn fetch_gitbutler_branches(
+    projects: State<'_, projects::Controller>,
+    users: State<'_, users::Controller>,
+    settings: State<'_, AppSettingsWithDiskSync>,
+    project_id: ProjectId,
+) -> Result<Vec<ServerBranch>, Error> {
+    let project = projects.get(project_id)?;
+    let ctx = CommandContext::open(&project, settings.get()?.clone())?;
+    let user = project_user(&users, &project)?;
+    Ok(gitbutler_sync::cloud::fetch_remote_branches(&ctx, &user)?)
+}
+
+#[tauri::command(async)]
+#[instrument(skip(projects, users, settings), err(Debug))]
+pub fn inbound_gitbutler_commits(
+    projects: State<'_, projects::Controller>,
+    users: State<'_, users::Controller>,
+    settings: State<'_, AppSettingsWithDiskSync>,
+    project_id: ProjectId,
+) -> Result<Vec<InboundRef>, Error> {
+    let project = projects.get(project_id)?;
+    let ctx = CommandContext::open(&project, settings.get()?.clone())?;
+    let user = project_user(&users, &project)?;
+    Ok(gitbutler_sync::inbound::inbound_commits(&ctx, &user)?)
+}
+
+#[tauri::command(async)]
+#[instrument(skip(projects, settings), err(Debug))]
+pub fn plan_code_push(
+    projects: State<'_, projects::Controller>,
+    settings: State<'_, AppSettingsWithDiskSync>,
+    project_id: ProjectId,
+) -> Result<SyncPlan, Error> {
+    let project = projects.get(project_id)?;
+    let ctx = CommandContext::open(&project, settings.get()?.clone())?;
+    Ok(gitbutler_sync::plan::create(&ctx)?)
+}
+
+#[tauri::command(async)]
+#[instrument(skip(projects, settings), err(Debug))]
+pub fn dry_run_code_push(
+    projects: State<'_, projects::Controller>,
+    settings: State<'_, AppSettingsWithDiskSync>,
+    project_id: ProjectId,
+) -> Result<Vec<Vec<String>>, Error> {
+    let project = projects.get(project_id)?;
+    let ctx = CommandContext::open(&project, settings.get()?.clone())?;
+    Ok(gitbutler_sync::plan::dry_run(&ctx, &|_event| {})?)
+}
+
+/// Push `plan`, one at a time with the pushes of the watcher of the project.
+#[tauri::command(async)]
+#[instrument(skip(windows, projects, settings, plan), err(Debug))]
+pub fn execute_code_push(
+    windows: State<'_, WindowState>,
+    projects: State<'_, projects::Controller>,
+    settings: State<'_, AppSettingsWithDiskSync>,
+    project_id: ProjectId,
+    plan: SyncPlan,
+) -> Result<(), Error> {
+    windows.handler(project_id)?.run_exclusive(
+        project_id,
+        settings.get()?.clone(),
+        |ctx, user, transport| {
+            gitbutler_sync::plan::execute(ctx, user, &projects, transport, &plan, &|_event| {})
+        },
+    )?;
+    Ok(())
+}
+
+/// Push all code of the project again, no matter what was pushed before, which sends a lot of data.
+#[tauri::command(async)]
+#[instrument(skip(windows, settings), err(Debug))]
+pub fn force_code_resync(
+    windows: State<'_, WindowState>,
+    settings: State<'_, AppSettingsWithDiskSync>,
+    project_id: ProjectId,
+) -> Result<(), Error> {
+    windows
+        .handler(project_id)?
+        .force_resync(project_id, settings.get()?.clone())?;
+    Ok(())
+}
+
+#[tauri::command(async)]
+#[instrument(skip(projects, settings), err(Debug))]
+pub fn code_push_commits_left(
+    projects: State<'_, projects::Controller>,
+    settings: State<'_, AppSettingsWithDiskSync>,
+    project_id: ProjectId,
+) -> Result<usize, Error> {
+    let project = projects.get(project_id)?;
+    let ctx = CommandContext::open(&project, settings.get()?.clone())?;
+    Ok(gitbutler_sync::cloud::commits_left_to_push(&ctx)?)
+}
+
+#[tauri::command(async)]
+#[instrument(skip(projects, settings), err(Debug))]
+pub fn code_push_refs(
+    projects: State<'_, projects::Controller>,
+    settings: State<'_, AppSettingsWithDiskSync>,
+    project_id: ProjectId,
+) -> Result<PushRefs, Error> {
+    let project = projects.get(project_id)?;
+    let ctx = CommandContext::open(&project, settings.get()?.clone())?;
+    Ok(gitbutler_sync::cloud::categorized_push_refs(&ctx)?)
+}
+
+#[tauri::command(async)]
+#[instrument(skip(projects, users, settings), err(Debug))]
+pub fn code_push_sync_status(
+    projects: State<'_, projects::Controller>,
+    users: State<'_, users::Controller>,
+    settings: State<'_, AppSettingsWithDiskSync>,
+    project_id: ProjectId,
+) -> Result<SyncStatus, Error> {
+    let project = projects.get(project_id)?;
+    let ctx = CommandContext::open(&project, settings.get()?.clone())?;
+    let user = users.get_user_for(project.account_id)?;
+    Ok(gitbutler_sync::cloud::sync_status(&ctx, user.as_ref())?)
+}
+
+#[tauri::command(async)]
+#[instrument(skip(projects), err(Debug))]
+pub fn saved_code_push_plan(
+    projects: State<'_, projects::Controller>,
+    project_id: ProjectId,
+) -> Result<Option<SyncPlan>, Error> {
+    let project = projects.get(project_id)?;
+    Ok(gitbutler_sync::plan::saved(&project)?)
+}
+
+/// Resume the saved plan, one at a time with the pushes of the watcher of the project.
+#[tauri::command(async)]
+#[instrument(skip(windows, projects, settings), err(Debug))]
+pub fn resume_code_push(
+    windows: State<'_, WindowState>,
+    projects: State<'_, projects::Controller>,
+    settings: State<'_, AppSettingsWithDiskSync>,
+    project_id: ProjectId,
+) -> Result<ResumeOutcome, Error> {
+    Ok(windows.handler(project_id)?.run_exclusive(
+        project_id,
+        settings.get()?.clone(),
+        |ctx, user, transport| {
+            gitbutler_sync::plan::resume(ctx, user, &projects, transport, &|_event| {})
+        },
+    )?)
+}
+
+/// Compare what was pushed with the server, one at a time with the pushes of the watcher of the
+/// project, as both record the push state.
+#[tauri::command(async)]
+#[instrument(skip(windows, projects, settings), err(Debug))]
+pub fn reconcile_code_push(
+    windows: State<'_, WindowState>,
+    projects: State<'_, projects::Controller>,
+    settings: State<'_, AppSettingsWithDiskSync>,
+    project_id: ProjectId,
+) -> Result<Reconciliation, Error> {
+    Ok(windows.handler(project_id)?.run_exclusive(
+        project_id,
+        settings.get()?.clone(),
+        |ctx, user, transport| {
+            gitbutler_sync::reconcile::reconcile_via(ctx, user, &projects, transport)
+        },
+    )?)
+}
+
+/// Return the user whose credentials are used for pushing `project` and fetching from it,
+/// which is the one of the account the project is associated with.
+pub(crate) fn project_user(
+    users: &users::Controller,
+    project: &projects::Project,
+) -> anyhow::Result<User> {
+    users
+        .get_user_for(project.account_id)?
+        .ok_or_else(|| {
+            anyhow!(
+                "Cannot sync project '{}' without being logged in",
+                project.title
+            )
+        })
+        .context(Code::ProjectGitAuth)
+}
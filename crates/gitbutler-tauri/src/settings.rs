@@ -1,5 +1,6 @@
 #![allow(deprecated)]
 use anyhow::Result;
+use but_settings::api::CodePushUpdate;
 use but_settings::api::FeatureFlagsUpdate;
 use but_settings::api::TelemetryUpdate;
 use but_settings::AppSettings;
@@ -79,3 +80,12 @@ pub fn update_feature_flags(
 ) -> Result<(), Error> {
     handle.update_feature_flags(update).map_err(|e| e.into())
 }
+
+#[tauri::command(async)]
+#[instrument(skip(handle), err(Debug))]
+pub fn update_code_push(
+    handle: State<'_, AppSettingsWithDiskSync>,
+    update: CodePushUpdate,
+) -> Result<(), Error> {
+    handle.update_code_push(update).map_err(|e| e.into())
+}
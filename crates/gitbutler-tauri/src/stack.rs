@@ -4,10 +4,11 @@ use gitbutler_command_context::CommandContext;
 use gitbutler_project as projects;
 use gitbutler_project::ProjectId;
 use gitbutler_stack::StackId;
-use gitbutler_user::User;
+use gitbutler_user::{self as users, User};
 use tauri::State;
 use tracing::instrument;
 
+use crate::remotes::project_user;
 use crate::virtual_branches::commands::emit_vbranches;
 use crate::{error::Error, WindowState};
 
@@ -140,3 +141,20 @@ pub fn push_stack_to_review(
     emit_vbranches(&windows, project_id, ctx.app_settings());
     Ok(review_id)
 }
+
+#[tauri::command(async)]
+#[instrument(skip(projects, users, settings), err(Debug))]
+pub fn push_stack_to_gitbutler(
+    projects: State<'_, projects::Controller>,
+    users: State<'_, users::Controller>,
+    settings: State<'_, AppSettingsWithDiskSync>,
+    project_id: ProjectId,
+    stack_id: StackId,
+) -> Result<bool, Error> {
+    let project = projects.get(project_id)?;
+    let ctx = CommandContext::open(&project, settings.get()?.clone())?;
+    let user = project_user(&users, &project)?;
+    Ok(gitbutler_sync::cloud::push_virtual_branch(
+        &ctx, &user, stack_id,
+    )?)
+}
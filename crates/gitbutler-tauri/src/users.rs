@@ -35,6 +35,26 @@ pub mod commands {
         Ok(())
     }
 
+    #[tauri::command(async)]
+    #[instrument(skip(login), err(Debug))]
+    pub fn list_accounts(login: State<'_, Controller>) -> Result<Vec<User>, Error> {
+        Ok(login.accounts()?)
+    }
+
+    #[tauri::command(async)]
+    #[instrument(skip(login), err(Debug))]
+    pub fn add_account(login: State<'_, Controller>, user: User) -> Result<User, Error> {
+        login.add_account(&user)?;
+        Ok(user)
+    }
+
+    #[tauri::command(async)]
+    #[instrument(skip(login), err(Debug))]
+    pub fn delete_account(login: State<'_, Controller>, id: u64) -> Result<(), Error> {
+        login.delete_account(id)?;
+        Ok(())
+    }
+
     #[derive(Debug, Deserialize, Serialize)]
     pub struct UserWithSecrets {
         id: u64,
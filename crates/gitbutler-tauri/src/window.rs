@@ -67,6 +67,11 @@ pub(crate) mod state {
                         payload: serde_json::json!(&but_core::ui::WorktreeChanges::from(changes)),
                         project_id,
                     },
+                    Change::CodePush { project_id, event } => ChangeForFrontend {
+                        name: format!("project://{}/code-push", project_id),
+                        payload: serde_json::json!(event),
+                        project_id,
+                    },
                 }
             }
         }
@@ -99,6 +104,8 @@ pub(crate) mod state {
         project_id: ProjectId,
         /// The watcher of the currently active project.
         watcher: gitbutler_watcher::WatcherHandle,
+        /// The handler of the watcher, to run pushes requested by the user one at a time with its own.
+        handler: gitbutler_watcher::Handler,
         /// An active lock to signal that the entire project is locked for the Window this state belongs to.
         exclusive_access: gitbutler_project::access::LockFile,
     }
@@ -165,7 +172,7 @@ pub(crate) mod state {
             let worktree_dir = project.path.clone();
             let project_id = project.id;
             let watcher = gitbutler_watcher::watch_in_background(
-                handler,
+                handler.clone(),
                 worktree_dir,
                 project_id,
                 app_settings,
@@ -175,6 +182,7 @@ pub(crate) mod state {
                 State {
                     project_id,
                     watcher,
+                    handler,
                     exclusive_access,
                 },
             );
@@ -197,6 +205,19 @@ pub(crate) mod state {
             }
         }
 
+        /// Return the handler of the watcher of the project with `project_id`, which runs the
+        /// pushes of the project.
+        pub fn handler(&self, project_id: ProjectId) -> Result<gitbutler_watcher::Handler> {
+            let state_by_label = self.state.lock();
+            state_by_label
+                .values()
+                .find(|state| state.project_id == project_id)
+                .map(|state| state.handler.clone())
+                .ok_or_else(|| {
+                    anyhow::anyhow!("matching watcher of project not found, wanted {project_id}")
+                })
+        }
+
         /// Flush file-monitor watcher events once the windows regains focus for it to respond instantly
         /// instead of according to the tick-rate.
         pub fn flush(&self, window: &WindowLabelRef) -> Result<()> {
@@ -44,24 +44,103 @@ impl Controller {
 
     pub fn delete_user(&self) -> Result<()> {
         self.storage.delete().context("failed to delete user")?;
-        let namespace = secret::Namespace::BuildKind;
-        secret::delete(User::ACCESS_TOKEN_HANDLE, namespace).ok();
-        secret::delete(User::GITHUB_ACCESS_TOKEN_HANDLE, namespace).ok();
+        delete_secrets(&User::default());
+        Ok(())
+    }
+
+    /// Return the user whose credentials are used for a project associated with `account_id`,
+    /// which is the current login if `account_id` is `None` or its id.
+    ///
+    /// Return `None` if there is no such user.
+    pub fn get_user_for(&self, account_id: Option<u64>) -> Result<Option<User>> {
+        let login = self.get_user()?;
+        let Some(account_id) = account_id else {
+            return Ok(login);
+        };
+        if login.as_ref().is_some_and(|login| login.id == account_id) {
+            return Ok(login);
+        }
+        Ok(self
+            .accounts()?
+            .into_iter()
+            .find(|account| account.id == account_id))
+    }
+
+    /// Read `user` from storage again and return it if its access token changed since `user` was
+    /// obtained, e.g. as it logged in again after its token expired or was revoked.
+    /// Tokens aren't renewed here, this only picks up what was stored in the meantime.
+    ///
+    /// Return `None` if the stored token is the same, so using it again wouldn't help.
+    pub fn reloaded(&self, user: &User) -> Result<Option<User>> {
+        let Some(current) = self.get_user_for(Some(user.id))? else {
+            return Ok(None);
+        };
+        if current.access_token()?.0 == user.access_token()?.0 {
+            return Ok(None);
+        }
+        Ok(Some(current))
+    }
+
+    /// Return the accounts that are known in addition to the current login.
+    pub fn accounts(&self) -> Result<Vec<User>> {
+        self.storage.accounts().context("failed to get accounts")
+    }
+
+    /// Add `account` to the known accounts, or update it if one with the same id is known already.
+    ///
+    /// Like with [Self::set_user()], its secrets are stored in the secrets store.
+    pub fn add_account(&self, account: &User) -> Result<()> {
+        let account = User {
+            is_account: true,
+            ..account.clone()
+        };
+        persist_secrets(&account);
+        let mut accounts = self.accounts()?;
+        accounts.retain(|known| known.id != account.id);
+        accounts.push(account);
+        self.storage
+            .set_accounts(&accounts)
+            .context("failed to add account")
+    }
+
+    /// Forget the account with `id` along with its secrets.
+    pub fn delete_account(&self, id: u64) -> Result<()> {
+        let mut accounts = self.accounts()?;
+        let Some(idx) = accounts.iter().position(|account| account.id == id) else {
+            return Ok(());
+        };
+        let account = accounts.remove(idx);
+        self.storage
+            .set_accounts(&accounts)
+            .context("failed to delete account")?;
+        delete_secrets(&account);
         Ok(())
     }
 }
 
-/// As `user` sports interior mutability right now, let's play it safe and work with fully owned items only.
-fn write_without_secrets_if_secrets_present(storage: &Storage, user: User) -> Result<bool> {
-    let mut needs_write = false;
+/// Store the secrets of `user` in the secrets store, returning `true` if any of them was stored.
+fn persist_secrets(user: &User) -> bool {
+    let mut persisted = false;
     let namespace = secret::Namespace::BuildKind;
     if let Some(gb_token) = user.access_token.borrow_mut().take() {
-        needs_write |= secret::persist(User::ACCESS_TOKEN_HANDLE, &gb_token, namespace).is_ok();
+        persisted |= secret::persist(&user.access_token_handle(), &gb_token, namespace).is_ok();
     }
     if let Some(gh_token) = user.github_access_token.borrow_mut().take() {
-        needs_write |=
-            secret::persist(User::GITHUB_ACCESS_TOKEN_HANDLE, &gh_token, namespace).is_ok();
+        persisted |=
+            secret::persist(&user.github_access_token_handle(), &gh_token, namespace).is_ok();
     }
+    persisted
+}
+
+fn delete_secrets(user: &User) {
+    let namespace = secret::Namespace::BuildKind;
+    secret::delete(&user.access_token_handle(), namespace).ok();
+    secret::delete(&user.github_access_token_handle(), namespace).ok();
+}
+
+/// As `user` sports interior mutability right now, let's play it safe and work with fully owned items only.
+fn write_without_secrets_if_secrets_present(storage: &Storage, user: User) -> Result<bool> {
+    let needs_write = persist_secrets(&user);
     if needs_write {
         storage.set(&user)?;
     }
@@ -5,6 +5,7 @@ use anyhow::Result;
 use crate::User;
 
 const USER_FILE: &str = "user.json";
+const ACCOUNTS_FILE: &str = "accounts.json";
 
 #[derive(Debug, Clone)]
 pub(crate) struct Storage {
@@ -33,4 +34,20 @@ impl Storage {
     pub fn delete(&self) -> Result<()> {
         Ok(self.inner.delete(USER_FILE)?)
     }
+
+    pub fn accounts(&self) -> Result<Vec<User>> {
+        let mut accounts: Vec<User> = match self.inner.read(ACCOUNTS_FILE)? {
+            Some(data) => serde_json::from_str(&data)?,
+            None => Vec::new(),
+        };
+        for account in &mut accounts {
+            account.is_account = true;
+        }
+        Ok(accounts)
+    }
+
+    pub fn set_accounts(&self, accounts: &[User]) -> Result<()> {
+        let data = serde_json::to_string(accounts)?;
+        Ok(self.inner.write(ACCOUNTS_FILE, &data)?)
+    }
 }
@@ -1,4 +1,4 @@
-use std::cell::RefCell;
+use std::{borrow::Cow, cell::RefCell};
 
 use anyhow::{Context, Result};
 use gitbutler_secret::{secret, Sensitive};
@@ -26,12 +26,34 @@ pub struct User {
     pub(super) github_access_token: RefCell<Option<Sensitive<String>>>,
     #[serde(default)]
     pub github_username: Option<String>,
+    /// `true` if this is one of the [additional accounts](crate::Controller::accounts()) rather
+    /// than the login, which decides where its secrets are stored.
+    #[serde(skip)]
+    pub(super) is_account: bool,
 }
 
 impl User {
     pub(super) const ACCESS_TOKEN_HANDLE: &'static str = "gitbutler_access_token";
     pub(super) const GITHUB_ACCESS_TOKEN_HANDLE: &'static str = "github_access_token";
 
+    /// The handle of the access token in the secrets store.
+    pub(super) fn access_token_handle(&self) -> Cow<'static, str> {
+        self.handle(Self::ACCESS_TOKEN_HANDLE)
+    }
+
+    /// The handle of the GitHub access token in the secrets store.
+    pub(super) fn github_access_token_handle(&self) -> Cow<'static, str> {
+        self.handle(Self::GITHUB_ACCESS_TOKEN_HANDLE)
+    }
+
+    fn handle(&self, login_handle: &'static str) -> Cow<'static, str> {
+        if self.is_account {
+            format!("{login_handle}_{}", self.id).into()
+        } else {
+            login_handle.into()
+        }
+    }
+
     /// Return the access token of the user after fetching it from the secrets store.
     ///
     /// It's cached after the first retrieval.
@@ -40,7 +62,7 @@ impl User {
             return Ok(token.clone());
         }
         let err_msg = "access token for user was deleted from keychain - login is now invalid";
-        let secret = secret::retrieve(Self::ACCESS_TOKEN_HANDLE, secret::Namespace::BuildKind)?
+        let secret = secret::retrieve(&self.access_token_handle(), secret::Namespace::BuildKind)?
             .context(err_msg)?;
         *self.access_token.borrow_mut() = Some(secret.clone());
         Ok(secret)
@@ -54,7 +76,7 @@ impl User {
             return Ok(Some(token.clone()));
         }
         let secret = secret::retrieve(
-            Self::GITHUB_ACCESS_TOKEN_HANDLE,
+            &self.github_access_token_handle(),
             secret::Namespace::BuildKind,
         )?;
         self.github_access_token.borrow_mut().clone_from(&secret);
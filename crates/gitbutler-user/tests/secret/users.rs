@@ -112,6 +112,79 @@ fn auto_migration_of_secrets_on_when_getting_and_setting_user() -> anyhow::Resul
     Ok(())
 }
 
+#[test]
+#[serial]
+fn projects_use_the_credentials_of_their_account() -> anyhow::Result<()> {
+    credentials::setup();
+    let app_data = tempdir()?;
+    let users = gitbutler_user::Controller::from_path(app_data.path());
+
+    let login: User = serde_json::from_slice(&std::fs::read(user_fixture("login-only.v1"))?)?;
+    users.set_user(&login)?;
+    let mut work: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(user_fixture("login-only.v1"))?)?;
+    work["id"] = 42.into();
+    work["access_token"] = "work-token".into();
+    let work: User = serde_json::from_value(work)?;
+    users.add_account(&work)?;
+    assert_eq!(count_secrets(), 2, "each account has its own access token");
+
+    let token_for = |account_id| -> anyhow::Result<String> {
+        let user = users
+            .get_user_for(account_id)?
+            .expect("the account is known");
+        Ok(user.access_token()?.0)
+    };
+    assert_eq!(
+        token_for(None)?,
+        "aaaaaaaa-bbbb-cccc-dddd-eeeeeeeeeeee",
+        "projects without account use the login"
+    );
+    assert_eq!(
+        token_for(Some(login.id))?,
+        "aaaaaaaa-bbbb-cccc-dddd-eeeeeeeeeeee"
+    );
+    assert_eq!(token_for(Some(42))?, "work-token");
+    assert!(
+        users.get_user_for(Some(7))?.is_none(),
+        "unknown accounts aren't replaced"
+    );
+
+    users.delete_account(42)?;
+    assert!(users.get_user_for(Some(42))?.is_none());
+    assert_eq!(count_secrets(), 1, "the secrets of the login remain");
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn users_are_reloaded_once_their_token_changes() -> anyhow::Result<()> {
+    credentials::setup();
+    let app_data = tempdir()?;
+    let users = gitbutler_user::Controller::from_path(app_data.path());
+
+    let login: User = serde_json::from_slice(&std::fs::read(user_fixture("login-only.v1"))?)?;
+    users.set_user(&login)?;
+    let user = users.get_user()?.expect("the login is stored");
+    assert_eq!(
+        user.access_token()?.0,
+        "aaaaaaaa-bbbb-cccc-dddd-eeeeeeeeeeee"
+    );
+    assert!(
+        users.reloaded(&user)?.is_none(),
+        "the token didn't change, so there is nothing to reload"
+    );
+
+    let mut renewed: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(user_fixture("login-only.v1"))?)?;
+    renewed["access_token"] = "renewed-token".into();
+    users.set_user(&serde_json::from_value(renewed)?)?;
+    let reloaded = users.reloaded(&user)?.expect("the stored token changed");
+    assert_eq!(reloaded.access_token()?.0, "renewed-token");
+    assert!(users.reloaded(&reloaded)?.is_none());
+    Ok(())
+}
+
 fn user_fixture(name: &str) -> PathBuf {
     let fixture = Path::new("tests/fixtures/users").join(name);
     assert!(
@@ -109,4 +109,8 @@ pub enum Change {
         project_id: ProjectId,
         changes: but_core::WorktreeChanges,
     },
+    CodePush {
+        project_id: ProjectId,
+        event: gitbutler_sync::events::Event,
+    },
 }
@@ -1,19 +1,28 @@
 use std::{path::PathBuf, sync::Arc};
 
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
 use but_settings::{AppSettings, AppSettingsWithDiskSync};
 use gitbutler_branch_actions::{internal::StackListResult, VirtualBranches};
 use gitbutler_command_context::CommandContext;
 use gitbutler_diff::DiffByPathMap;
-use gitbutler_error::error::Marker;
+use gitbutler_error::error::{Code, Marker};
 use gitbutler_operating_modes::{in_open_workspace_mode, operating_mode};
 use gitbutler_oplog::{
     entry::{OperationKind, SnapshotDetails},
     OplogExt,
 };
 use gitbutler_project::{self as projects, Project, ProjectId};
-use gitbutler_sync::cloud::{push_oplog, push_repo};
+use gitbutler_sync::{
+    budget::{PushBudget, PushOutcome},
+    cloud::{
+        force_resync, push_oplog, push_repo_via, sync_now, sync_status, RemoteError, SyncOutcome,
+        SyncStatus,
+    },
+    queue::PushQueue,
+    transport::{CodePushTransport, GitTransport},
+};
 use gitbutler_user as users;
+use tokio_util::sync::CancellationToken;
 use tracing::instrument;
 
 use super::{events, Change};
@@ -30,6 +39,12 @@ pub struct Handler {
     // need extra protection.
     projects: projects::Controller,
     users: users::Controller,
+    /// Pushes to GitButler after oplog changes run one at a time, while those requested meanwhile
+    /// are coalesced into one that runs right after. Pushes requested by the user run one at a
+    /// time with them.
+    push_queue: Arc<PushQueue>,
+    /// Sends code to GitButler, which is the server unless replaced for testing.
+    transport: Arc<dyn CodePushTransport>,
 
     /// A function to send events - decoupled from app-handle for testing purposes.
     #[allow(clippy::type_complexity)]
@@ -47,16 +62,29 @@ impl Handler {
         Handler {
             projects,
             users,
+            push_queue: Arc::default(),
+            transport: Arc::new(GitTransport),
             send_event: Arc::new(send_event),
         }
     }
 
+    /// Send code to GitButler with `transport` instead of pushing to the server.
+    pub fn with_transport(mut self, transport: impl CodePushTransport + 'static) -> Self {
+        self.transport = Arc::new(transport);
+        self
+    }
+
     /// Handle the events that come in from the filesystem, or the public API.
-    #[instrument(skip(self, app_settings), fields(event = %event), err(Debug))]
+    ///
+    /// Long-running work, like pushing code, stops early once `cancellation` is cancelled.
+    /// Oplog changes that come in while pushing to GitButler don't push themselves, but make the running
+    /// push run once more when it's done, which then returns its result.
+    #[instrument(skip(self, app_settings, cancellation), fields(event = %event), err(Debug))]
     pub(super) fn handle(
         &self,
         event: events::InternalEvent,
         app_settings: AppSettingsWithDiskSync,
+        cancellation: &CancellationToken,
     ) -> Result<()> {
         match event {
             events::InternalEvent::ProjectFilesChange(project_id, paths) => {
@@ -70,12 +98,15 @@ impl Handler {
                     .context("failed to handle git file change event")
             }
             events::InternalEvent::GitButlerOplogChange(project_id) => {
-                let ctx = self.open_command_context(project_id, app_settings.get()?.clone())?;
-                self
-                .gitbutler_oplog_change(&ctx)
-                .context("failed to handle gitbutler oplog change event")
+                let app_settings = app_settings.get()?.clone();
+                self.push_queue
+                    .run(|| {
+                        // Each push sees the project as left behind by the previous one.
+                        let ctx = self.open_command_context(project_id, app_settings.clone())?;
+                        self.gitbutler_oplog_change(&ctx, cancellation)
+                    })
+                    .context("failed to handle gitbutler oplog change event")
             }
-            ,
 
             // This is only produced at the end of mutating Tauri commands to trigger a fresh state being served to the UI.
             events::InternalEvent::CalculateVirtualBranches(project_id) => {
@@ -86,6 +117,113 @@ impl Handler {
         }
     }
 
+    /// Return whether the code of the project with `project_id` is synced when its oplog changes,
+    /// and if not, why, using the same account as the pushes.
+    pub fn sync_status(
+        &self,
+        project_id: ProjectId,
+        app_settings: AppSettings,
+    ) -> Result<SyncStatus> {
+        let ctx = self.open_command_context(project_id, app_settings)?;
+        let user = self.users.get_user_for(ctx.project().account_id)?;
+        sync_status(&ctx, user.as_ref())
+    }
+
+    /// Push the project with `project_id` to GitButler right away and return what the push did,
+    /// as requested by the user.
+    ///
+    /// A push that is running already, like one of an oplog change, is waited for first, and the
+    /// minimum interval between pushes doesn't apply. Credentials the server doesn't accept are
+    /// handled like with pushes of oplog changes.
+    pub fn sync_now(
+        &self,
+        project_id: ProjectId,
+        app_settings: AppSettings,
+    ) -> Result<SyncOutcome> {
+        self.push_queue.run_now(|| {
+            let ctx = self.open_command_context(project_id, app_settings.clone())?;
+            let project = ctx.project();
+            if !project.code_sync_enabled() {
+                return Err(anyhow!(
+                    "Cannot sync project '{}' as syncing its code is turned off",
+                    project.title
+                )
+                .context(Code::Validation));
+            }
+            let user = self.user_for(project)?;
+            self.with_reloaded_credentials(project, &user, |user| {
+                if project.oplog_sync_enabled() {
+                    push_oplog(&ctx, user)?;
+                }
+                sync_now(
+                    &ctx,
+                    user,
+                    &self.projects,
+                    self.transport.as_ref(),
+                    &CancellationToken::new(),
+                    &|event| {
+                        let _ = self.emit_app_event(Change::CodePush { project_id, event });
+                    },
+                )
+            })
+        })
+    }
+
+    /// Run `op` with the project with `project_id`, the user whose credentials its pushes use and
+    /// the transport pushes go through, while no other push of the project runs, and return its result.
+    ///
+    /// This is for requests of the user which change what pushes rely on, like executing a plan.
+    /// A running push is waited for first, and pushes of oplog changes requested meanwhile run once
+    /// `op` is done.
+    pub fn run_exclusive<T>(
+        &self,
+        project_id: ProjectId,
+        app_settings: AppSettings,
+        op: impl FnOnce(&CommandContext, &users::User, &dyn CodePushTransport) -> Result<T>,
+    ) -> Result<T> {
+        self.push_queue.run_exclusive(
+            || {
+                let ctx = self.open_command_context(project_id, app_settings.clone())?;
+                let user = self.user_for(ctx.project())?;
+                op(&ctx, &user, self.transport.as_ref())
+            },
+            || {
+                let ctx = self.open_command_context(project_id, app_settings.clone())?;
+                self.gitbutler_oplog_change(&ctx, &CancellationToken::new())
+            },
+        )
+    }
+
+    /// Push all code of the project with `project_id` again, no matter what was pushed before, and
+    /// return what the push did. See [`force_resync()`], which sends a lot of data.
+    ///
+    /// It runs exclusively like [`Handler::run_exclusive()`], so a push of an oplog change that
+    /// runs meanwhile can't record the push state from before the resync once it's done.
+    pub fn force_resync(
+        &self,
+        project_id: ProjectId,
+        app_settings: AppSettings,
+    ) -> Result<PushOutcome> {
+        self.run_exclusive(project_id, app_settings, |ctx, user, transport| {
+            force_resync(ctx, user, &self.projects, transport, &|event| {
+                let _ = self.emit_app_event(Change::CodePush { project_id, event });
+            })
+        })
+    }
+
+    /// Return the user whose credentials are used for pushing `project`, or fail if there is none.
+    fn user_for(&self, project: &Project) -> Result<users::User> {
+        self.users
+            .get_user_for(project.account_id)?
+            .ok_or_else(|| {
+                anyhow!(
+                    "Cannot sync project '{}' without being logged in",
+                    project.title
+                )
+            })
+            .context(Code::ProjectGitAuth)
+    }
+
     fn emit_app_event(&self, event: Change) -> Result<()> {
         (self.send_event)(event).context("failed to send event")
     }
@@ -231,16 +369,97 @@ impl Handler {
     }
 
     /// Invoked whenever there's a new oplog entry.
-    /// If synchronizing with GitButler's servers is enabled it will push Oplog refs
-    fn gitbutler_oplog_change(&self, ctx: &CommandContext) -> Result<()> {
-        if let Some(user) = self.users.get_user()? {
-            if ctx.project().oplog_sync_enabled() {
-                push_oplog(ctx, &user)?;
-            }
-            if ctx.project().code_sync_enabled() {
-                push_repo(ctx, &user, &self.projects)?;
+    /// If synchronizing with GitButler's servers is enabled it will push Oplog refs,
+    /// using the credentials of the account the project is associated with.
+    /// Code pushes stop between batches once `cancellation` is cancelled, to resume with the next change.
+    ///
+    /// If the server doesn't accept the credentials, they are read from storage again and, if the user
+    /// logged in again in the meantime, the push is retried with the new ones, but only once.
+    fn gitbutler_oplog_change(
+        &self,
+        ctx: &CommandContext,
+        cancellation: &CancellationToken,
+    ) -> Result<()> {
+        let Some(user) = self.users.get_user_for(ctx.project().account_id)? else {
+            return Ok(());
+        };
+        self.with_reloaded_credentials(ctx.project(), &user, |user| {
+            self.push_to_gitbutler(ctx, user, cancellation)
+        })
+    }
+
+    /// Run `push` of `project` with `user`, and if the server doesn't accept its credentials, read
+    /// them from storage again and, if the user logged in again in the meantime, retry `push` with
+    /// the new ones, but only once.
+    fn with_reloaded_credentials<T>(
+        &self,
+        project: &Project,
+        user: &users::User,
+        mut push: impl FnMut(&users::User) -> Result<T>,
+    ) -> Result<T> {
+        match push(user) {
+            Err(err) if is_auth_failure(&err) => {
+                // The user may have logged in again since their token expired.
+                let Some(reloaded) = self.users.reloaded(user)? else {
+                    return Err(auth_failure(err, project));
+                };
+                tracing::info!(
+                    project_id = %project.id,
+                    "retrying push with the credentials stored since",
+                );
+                push(&reloaded).map_err(|err| {
+                    if is_auth_failure(&err) {
+                        auth_failure(err, project)
+                    } else {
+                        err
+                    }
+                })
             }
+            res => res,
+        }
+    }
+
+    fn push_to_gitbutler(
+        &self,
+        ctx: &CommandContext,
+        user: &users::User,
+        cancellation: &CancellationToken,
+    ) -> Result<()> {
+        if ctx.project().oplog_sync_enabled() {
+            push_oplog(ctx, user)?;
+        }
+        if ctx.project().code_sync_enabled() {
+            // Large pushes are spread over multiple oplog changes, each resuming the previous one.
+            let budget = PushBudget::from_settings(&ctx.app_settings().code_push);
+            push_repo_via(
+                ctx,
+                user,
+                &self.projects,
+                &budget,
+                cancellation,
+                self.transport.as_ref(),
+                &|event| {
+                    let _ = self.emit_app_event(Change::CodePush {
+                        project_id: ctx.project().id,
+                        event,
+                    });
+                },
+            )?;
         }
         Ok(())
     }
 }
+
+fn is_auth_failure(err: &anyhow::Error) -> bool {
+    matches!(
+        err.downcast_ref::<RemoteError>(),
+        Some(RemoteError::Auth(_))
+    )
+}
+
+fn auth_failure(err: anyhow::Error, project: &Project) -> anyhow::Error {
+    err.context(format!(
+        "GitButler didn't accept the credentials used for project '{}' ({}), please log in again",
+        project.title, project.id
+    ))
+}
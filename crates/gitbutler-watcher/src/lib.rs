@@ -3,13 +3,14 @@
 #![allow(clippy::doc_markdown, clippy::missing_errors_doc)]
 
 mod events;
-use std::path::Path;
+use std::{path::Path, time::Instant};
 
 use anyhow::{Context, Result};
 use but_settings::AppSettingsWithDiskSync;
 use events::InternalEvent;
 pub use events::{Action, Change};
 use gitbutler_project::ProjectId;
+use gitbutler_sync::debounce::PushDebounce;
 pub use handler::Handler;
 use tokio::{
     sync::mpsc::{unbounded_channel, UnboundedSender},
@@ -72,6 +73,9 @@ impl WatcherHandle {
 /// up if they take longer to process than the 100ms window between them, causing high-CPU and possibly
 /// high-memory. However, the likelihood for this is much lower than it was before the architecture
 /// was changed to what it is now, which should be much less wasteful.
+///
+/// Oplog changes, which push the project to GitButler, are held back until no more came in for a
+/// while, as configured by the code push settings when the watcher starts, so bursts of them push once.
 pub fn watch_in_background(
     handler: handler::Handler,
     worktree_path: impl AsRef<Path>,
@@ -90,23 +94,42 @@ pub fn watch_in_background(
         signal_flush: flush_tx,
         cancellation_token: cancellation_token.clone(),
     };
-    let handle_event =
+    let handle_event = {
+        let cancellation_token = cancellation_token.clone();
         move |event: InternalEvent, app_settings: AppSettingsWithDiskSync| -> Result<()> {
             let handler = handler.clone();
+            let cancellation_token = cancellation_token.clone();
             // NOTE: Traditional parallelization (blocking) is required as `tokio::spawn()` on
             //       the `handler.handle()` future isn't `Send` as it keeps non-Send things
             //       across await points. Further, there is a fair share of `sync` IO happening
             //       as well, so nothing can really be done here.
             task::spawn_blocking(move || {
-                handler.handle(event, app_settings).ok();
+                handler
+                    .handle(event, app_settings, &cancellation_token)
+                    .ok();
             });
             Ok(())
-        };
+        }
+    };
 
+    let mut push_debounce = PushDebounce::from_settings(&app_settings.get()?.code_push);
     tokio::spawn(async move {
         loop {
             tokio::select! {
-                Some(event) = events_in.recv() => handle_event(event, app_settings.clone())?,
+                Some(event) = events_in.recv() => match event {
+                    InternalEvent::GitButlerOplogChange(project_id) => {
+                        push_debounce.request(project_id, Instant::now());
+                    }
+                    event => handle_event(event, app_settings.clone())?,
+                },
+                () = sleep_until(push_debounce.next_due()) => {
+                    for project_id in push_debounce.take_due(Instant::now()) {
+                        handle_event(
+                            InternalEvent::GitButlerOplogChange(project_id),
+                            app_settings.clone(),
+                        )?;
+                    }
+                }
                 Some(_signal_flush) = flush_rx.recv() => {
                     debounce.flush_nonblocking();
                 }
@@ -121,3 +144,11 @@ pub fn watch_in_background(
 
     Ok(handle)
 }
+
+/// Wait until `due`, or forever if there is nothing to wait for.
+async fn sleep_until(due: Option<Instant>) {
+    match due {
+        Some(due) => tokio::time::sleep_until(due.into()).await,
+        None => std::future::pending().await,
+    }
+}